@@ -0,0 +1,472 @@
+//! A stand-in for LiveU Central so the bot can be run without a real unit.
+//!
+//! Start it with `cargo run --example mock_liveu -- [address] [scenario.json]`
+//! and point the endpoints in `config.json` at it:
+//!
+//! ```json
+//! "endpoints": {
+//!     "api": "http://127.0.0.1:8080/v0",
+//!     "apiV2": "http://127.0.0.1:8080/v2",
+//!     "login": "http://127.0.0.1:8080/login",
+//!     "applicationId": "mock"
+//! }
+//! ```
+//!
+//! The units can be scripted while the mock is running:
+//!
+//! - `PUT /mock/units/{id}` merges the JSON body into the unit, e.g.
+//!   `{"online": false}`, `{"camera": false}` or `{"failNext": [503, 503]}`
+//! - `POST /mock/expire-tokens` expires every issued token so the next
+//!   request gets a 401
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::Incoming, server::conn::http1, service::service_fn, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpListener, sync::Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Scenario {
+    #[serde(default = "default_token_ttl")]
+    token_ttl: u64,
+    units: Vec<MockUnit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MockUnit {
+    id: String,
+    name: String,
+    reg_code: String,
+    #[serde(default = "default_inventory")]
+    inventory: String,
+    #[serde(default = "default_true")]
+    online: bool,
+    #[serde(default = "default_true")]
+    camera: bool,
+    #[serde(default)]
+    streaming: bool,
+    #[serde(default = "default_delay")]
+    delay: u64,
+    #[serde(default = "default_reboot_secs")]
+    reboot_secs: u64,
+    /// Status codes returned instead of a real response, one per request
+    #[serde(default)]
+    fail_next: Vec<u16>,
+    battery: Value,
+    interfaces: Value,
+    #[serde(skip)]
+    rebooting_until: Option<Instant>,
+}
+
+fn default_token_ttl() -> u64 {
+    3600
+}
+
+fn default_inventory() -> String {
+    "Mock Inventory".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_delay() -> u64 {
+    1000
+}
+
+fn default_reboot_secs() -> u64 {
+    40
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            token_ttl: default_token_ttl(),
+            units: vec![MockUnit {
+                id: "mock-boss-1".to_string(),
+                name: "Mock LU600".to_string(),
+                reg_code: "MOCK0001".to_string(),
+                inventory: default_inventory(),
+                online: true,
+                camera: true,
+                streaming: false,
+                delay: default_delay(),
+                reboot_secs: default_reboot_secs(),
+                fail_next: Vec::new(),
+                battery: json!({
+                    "connected": true,
+                    "percentage": 87,
+                    "runTimeToEmpty": 143,
+                    "discharging": true,
+                    "charging": false
+                }),
+                interfaces: json!([
+                    mock_interface("0", "4G", 2400),
+                    mock_interface("1", "5G", 3100),
+                    mock_interface("wlan0", "", 1800)
+                ]),
+                rebooting_until: None,
+            }],
+        }
+    }
+}
+
+fn mock_interface(port: &str, technology: &str, kbps: u32) -> Value {
+    json!({
+        "connected": true,
+        "name": format!("Modem {}", port),
+        "downlinkKbps": kbps * 2,
+        "uplinkKbps": kbps,
+        "enabled": true,
+        "port": port,
+        "technology": technology,
+        "upSignalQuality": 4,
+        "downSignalQuality": 4,
+        "activeSim": null,
+        "isCurrentlyRoaming": false,
+        "kbps": kbps,
+        "signalQuality": 4
+    })
+}
+
+impl MockUnit {
+    /// Brings the unit back once a reboot is done
+    fn tick(&mut self) {
+        if let Some(until) = self.rebooting_until {
+            if Instant::now() >= until {
+                self.rebooting_until = None;
+                self.online = true;
+            }
+        }
+    }
+
+    fn interfaces(&self) -> Value {
+        let mut interfaces = self.interfaces.clone();
+
+        if !self.streaming {
+            if let Some(list) = interfaces.as_array_mut() {
+                for interface in list {
+                    interface["uplinkKbps"] = json!(0);
+                    interface["kbps"] = json!(0);
+                }
+            }
+        }
+
+        interfaces
+    }
+
+    fn video(&self) -> Value {
+        json!({
+            "resolution": if self.camera { Some("1920x1080") } else { None },
+            "bitrate": if self.streaming { Some(5000) } else { None },
+        })
+    }
+}
+
+struct State {
+    scenario: Scenario,
+    tokens: HashMap<String, Instant>,
+    issued: u64,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let scenario = match args.next() {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => Scenario::default(),
+    };
+
+    println!("Mock LiveU Central listening on http://{}", address);
+    for unit in &scenario.units {
+        println!(
+            "Unit {} ({}) with boss id {}",
+            unit.name, unit.reg_code, unit.id
+        );
+    }
+
+    let state = Arc::new(Mutex::new(State {
+        scenario,
+        tokens: HashMap::new(),
+        issued: 0,
+    }));
+    let listener = TcpListener::bind(&address).await?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(state.clone(), req));
+
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    state: SharedState,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or("").to_string();
+    let authorization = req
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.to_string());
+    let body = match req.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(_) => return Ok(empty(StatusCode::BAD_REQUEST)),
+    };
+
+    let mut state = state.lock().await;
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    println!("{} {}", method, req_line(&path, &query));
+
+    let res = match (&method, segments.as_slice()) {
+        (&Method::POST, ["login"]) => login(&mut state),
+        (&Method::POST, ["mock", "expire-tokens"]) => {
+            state.tokens.clear();
+            empty(StatusCode::NO_CONTENT)
+        }
+        (&Method::PUT, ["mock", "units", id]) => patch_unit(&mut state, id, &body),
+        (_, [version, rest @ ..]) if *version == "v0" || *version == "v2" => {
+            if !is_authorized(&state, authorization.as_deref()) {
+                return Ok(empty(StatusCode::UNAUTHORIZED));
+            }
+
+            api(&mut state, &method, rest, &query, &body)
+        }
+        _ => empty(StatusCode::NOT_FOUND),
+    };
+
+    Ok(res)
+}
+
+fn req_line(path: &str, query: &str) -> String {
+    if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, query)
+    }
+}
+
+fn login(state: &mut State) -> Response<Full<Bytes>> {
+    state.issued += 1;
+    let token = format!("mock-token-{}", state.issued);
+    let ttl = state.scenario.token_ttl;
+
+    state
+        .tokens
+        .insert(token.clone(), Instant::now() + Duration::from_secs(ttl));
+
+    json_response(
+        StatusCode::OK,
+        json!({ "data": { "response": { "access_token": token, "expires_in": ttl } } }),
+    )
+}
+
+fn is_authorized(state: &State, token: Option<&str>) -> bool {
+    token
+        .and_then(|token| state.tokens.get(token))
+        .map(|expires| Instant::now() < *expires)
+        .unwrap_or(false)
+}
+
+fn patch_unit(state: &mut State, id: &str, body: &[u8]) -> Response<Full<Bytes>> {
+    let patch: Value = match serde_json::from_slice(body) {
+        Ok(Value::Object(patch)) => Value::Object(patch),
+        _ => return empty(StatusCode::BAD_REQUEST),
+    };
+
+    let unit = match state.scenario.units.iter_mut().find(|u| u.id == id) {
+        Some(unit) => unit,
+        None => return empty(StatusCode::NOT_FOUND),
+    };
+
+    let mut current = serde_json::to_value(&*unit).expect("unit serializes");
+    if let (Some(current), Value::Object(patch)) = (current.as_object_mut(), patch) {
+        current.extend(patch);
+    }
+
+    match serde_json::from_value::<MockUnit>(current) {
+        Ok(patched) => {
+            *unit = MockUnit {
+                rebooting_until: unit.rebooting_until,
+                ..patched
+            };
+            json_response(StatusCode::OK, serde_json::to_value(&*unit).unwrap())
+        }
+        Err(_) => empty(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn api(
+    state: &mut State,
+    method: &Method,
+    path: &[&str],
+    query: &str,
+    body: &[u8],
+) -> Response<Full<Bytes>> {
+    if let (&Method::GET, ["inventories"]) = (method, path) {
+        let mut inventories: Vec<(String, Vec<Value>)> = Vec::new();
+
+        for unit in state.scenario.units.iter_mut() {
+            unit.tick();
+
+            let json = json!({
+                "id": unit.id,
+                "reg_code": unit.reg_code,
+                "status": if unit.online { "online" } else { "offline" },
+                "name": unit.name,
+            });
+
+            match inventories
+                .iter_mut()
+                .find(|(name, _)| *name == unit.inventory)
+            {
+                Some((_, units)) => units.push(json),
+                None => inventories.push((unit.inventory.to_owned(), vec![json])),
+            }
+        }
+
+        let inventories: Vec<Value> = inventories
+            .into_iter()
+            .enumerate()
+            .map(|(pos, (name, units))| {
+                json!({ "id": format!("inv-{}", pos + 1), "name": name, "units": units })
+            })
+            .collect();
+
+        return json_response(
+            StatusCode::OK,
+            json!({ "data": { "inventories": inventories } }),
+        );
+    }
+
+    let (id, rest) = match path {
+        ["units", id, rest @ ..] => (*id, rest),
+        _ => return empty(StatusCode::NOT_FOUND),
+    };
+
+    let unit = match state.scenario.units.iter_mut().find(|u| u.id == id) {
+        Some(unit) => unit,
+        None => return empty(StatusCode::NOT_FOUND),
+    };
+
+    unit.tick();
+
+    if !unit.fail_next.is_empty() {
+        let status = unit.fail_next.remove(0);
+        return empty(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    match (method, rest) {
+        (&Method::GET, []) if query.contains("fields=status") => json_response(
+            StatusCode::OK,
+            json!({ "data": { "unit": { "status": if unit.online { "online" } else { "offline" } } } }),
+        ),
+        (&Method::GET, []) if query.contains("fields=delay") => json_response(
+            StatusCode::OK,
+            json!({ "data": { "unit": { "delay": unit.delay } } }),
+        ),
+        (&Method::GET, ["status", "interfaces"]) if !unit.online => empty(StatusCode::NO_CONTENT),
+        (&Method::GET, ["status", "interfaces"]) => {
+            json_response(StatusCode::OK, unit.interfaces())
+        }
+        (&Method::GET, ["status", _]) if !unit.online => empty(StatusCode::NOT_FOUND),
+        (&Method::GET, ["status", "battery"]) => {
+            json_response(StatusCode::OK, unit.battery.clone())
+        }
+        (&Method::GET, ["status", "video"]) => json_response(StatusCode::OK, unit.video()),
+        (&Method::POST, ["stream"]) if !unit.online || !unit.camera => {
+            empty(StatusCode::BAD_REQUEST)
+        }
+        (&Method::POST, ["stream"]) => {
+            unit.streaming = true;
+            empty(StatusCode::CREATED)
+        }
+        (&Method::DELETE, ["stream"]) => {
+            unit.streaming = false;
+            empty(StatusCode::NO_CONTENT)
+        }
+        (&Method::POST, ["reboot"]) => {
+            unit.online = false;
+            unit.streaming = false;
+            unit.rebooting_until = Some(Instant::now() + Duration::from_secs(unit.reboot_secs));
+            empty(StatusCode::NO_CONTENT)
+        }
+        (&Method::PUT, ["interfaces", port]) => {
+            let enabled = serde_json::from_slice::<Value>(body)
+                .ok()
+                .and_then(|v| v["interface"]["enabled"].as_bool());
+
+            let interface = unit
+                .interfaces
+                .as_array_mut()
+                .and_then(|list| list.iter_mut().find(|i| i["port"] == *port));
+
+            match (enabled, interface) {
+                (Some(enabled), Some(interface)) => {
+                    interface["enabled"] = json!(enabled);
+                    interface["connected"] = json!(enabled);
+                    empty(StatusCode::NO_CONTENT)
+                }
+                (None, _) => empty(StatusCode::BAD_REQUEST),
+                (_, None) => empty(StatusCode::NOT_FOUND),
+            }
+        }
+        (&Method::PUT, ["delay"]) => {
+            let delay = serde_json::from_slice::<Value>(body)
+                .ok()
+                .and_then(|v| v["unit"]["delay"].as_u64());
+
+            match delay {
+                Some(delay) => {
+                    unit.delay = delay;
+                    empty(StatusCode::NO_CONTENT)
+                }
+                None => empty(StatusCode::BAD_REQUEST),
+            }
+        }
+        _ => empty(StatusCode::NOT_FOUND),
+    }
+}
+
+fn json_response(status: StatusCode, value: Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(value.to_string())))
+        .expect("valid response")
+}
+
+fn empty(status: StatusCode) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::new(Bytes::new()))
+        .expect("valid response")
+}
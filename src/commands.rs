@@ -0,0 +1,579 @@
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use twitch_irc::message::Badge;
+
+use crate::{
+    config,
+    error::Error,
+    units::{UnitEntry, Units},
+};
+
+/// Runs a command, the returned message is sent to chat
+pub type Handler<T> = fn(Arc<T>, Invocation) -> BoxFuture<'static, Result<String, Error>>;
+
+/// Roles in chat from lowest to highest, a command can be used by its role and every role above it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    /// The users in `adminUsers`
+    Admin,
+    Broadcaster,
+}
+
+impl Permission {
+    /// The highest role of the user based on their badges
+    pub fn of(login: &str, badges: &[Badge], admin_users: Option<&[String]>) -> Self {
+        let has_badge = |names: &[&str]| badges.iter().any(|b| names.contains(&b.name.as_str()));
+
+        if has_badge(&["broadcaster"]) {
+            Permission::Broadcaster
+        } else if admin_users
+            .is_some_and(|users| users.iter().any(|u| u.eq_ignore_ascii_case(login)))
+        {
+            Permission::Admin
+        } else if has_badge(&["moderator"]) {
+            Permission::Moderator
+        } else if has_badge(&["vip"]) {
+            Permission::Vip
+        } else if has_badge(&["subscriber", "founder"]) {
+            Permission::Subscriber
+        } else {
+            Permission::Everyone
+        }
+    }
+}
+
+/// Who may use a command
+#[derive(Debug, Clone)]
+pub struct Access {
+    pub level: Permission,
+    /// Users that may always use the command
+    pub allow: Vec<String>,
+    /// Users that may never use the command
+    pub deny: Vec<String>,
+}
+
+impl Access {
+    pub fn new(level: Permission) -> Self {
+        Access {
+            level,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+
+    pub fn allows(&self, login: &str, role: Permission) -> bool {
+        let listed = |users: &[String]| users.iter().any(|u| u.eq_ignore_ascii_case(login));
+
+        if listed(&self.deny) {
+            return false;
+        }
+
+        listed(&self.allow) || role >= self.level
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgKind {
+    /// Alias of a configured unit, ends up in `Invocation::unit`
+    Unit,
+    /// Custom or default port name, e.g. `sim1`
+    Port,
+    /// One of the words
+    OneOf(Vec<String>),
+    /// A number or one of the words
+    NumberOr(Vec<String>),
+    /// Anything
+    Text,
+    /// One or more words, e.g. a unit name with spaces. Arguments after it are taken from the end.
+    Words,
+}
+
+impl ArgKind {
+    fn matches(&self, value: &str, units: &Units, ports: &config::CustomUnitNames) -> bool {
+        match self {
+            ArgKind::Unit => units.find(value).is_some(),
+            // unit aliases take precedence over port names
+            ArgKind::Port => units.find(value).is_none() && ports.raw_port(value).is_some(),
+            ArgKind::OneOf(words) => words.iter().any(|w| w.eq_ignore_ascii_case(value)),
+            ArgKind::NumberOr(words) => {
+                value.parse::<u64>().is_ok() || words.iter().any(|w| w.eq_ignore_ascii_case(value))
+            }
+            ArgKind::Text | ArgKind::Words => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+impl Arg {
+    pub fn required(name: &'static str, kind: ArgKind) -> Self {
+        Arg {
+            name,
+            kind,
+            required: true,
+        }
+    }
+
+    pub fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Arg {
+            name,
+            kind,
+            required: false,
+        }
+    }
+
+    /// Optional unit alias, only shown in the usage when using multiple units
+    pub fn unit() -> Self {
+        Arg::optional("unit", ArgKind::Unit)
+    }
+
+    fn usage(&self) -> String {
+        let name = match &self.kind {
+            ArgKind::OneOf(words) => words.join("|"),
+            ArgKind::NumberOr(words) => {
+                let mut options = vec![self.name.to_string()];
+                options.extend(words.iter().cloned());
+                options.join("|")
+            }
+            _ => self.name.to_string(),
+        };
+
+        if self.required {
+            format!("<{}>", name)
+        } else {
+            format!("[{}]", name)
+        }
+    }
+}
+
+/// When a command runs as a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobMode {
+    Never,
+    Always,
+    /// Only when the argument is given, e.g. `!ludelay 2500` but not `!ludelay`
+    WithArg(&'static str),
+}
+
+/// A chat command and everything needed to run it
+pub struct CommandSpec<T> {
+    pub name: &'static str,
+    pub triggers: Vec<String>,
+    pub args: Vec<Arg>,
+    pub access: Access,
+    /// Seconds before the command can be used again
+    pub cooldown: u16,
+    /// Seconds before the same user can use the command again
+    pub user_cooldown: u16,
+    /// Runs as a job, no other job can run on the same unit until it's done
+    pub job: JobMode,
+    pub handler: Handler<T>,
+}
+
+impl<T> CommandSpec<T> {
+    pub fn usage(&self, units: &Units) -> String {
+        let mut usage = self.triggers.first().cloned().unwrap_or_default();
+
+        for arg in &self.args {
+            if arg.kind == ArgKind::Unit && !units.is_multi() {
+                continue;
+            }
+
+            usage += " ";
+            usage += &arg.usage();
+        }
+
+        usage
+    }
+
+    /// Whether the invocation changes the unit and runs as a job
+    pub fn is_job(&self, invocation: &Invocation) -> bool {
+        match self.job {
+            JobMode::Never => false,
+            JobMode::Always => true,
+            JobMode::WithArg(name) => invocation.arg(name).is_some(),
+        }
+    }
+
+    /// Matches the words after the trigger to the arguments
+    pub fn parse(
+        &self,
+        words: &[&str],
+        sender: &str,
+        channel: &str,
+        units: &Units,
+        ports: &config::CustomUnitNames,
+    ) -> Result<Invocation, Error> {
+        let mut invocation = Invocation {
+            sender: sender.to_owned(),
+            channel: channel.to_owned(),
+            unit: None,
+            args: HashMap::new(),
+        };
+
+        let takes_unit = self.args.iter().any(|a| a.kind == ArgKind::Unit);
+        let mut words = words.to_vec();
+
+        // the unit alias can be anywhere, e.g. `!lus cam2 sim1` and `!lus sim1 cam2`
+        if takes_unit {
+            if let Some(pos) = words.iter().position(|w| units.find(w).is_some()) {
+                invocation.unit = units.find(words.remove(pos));
+            }
+        }
+
+        let args: Vec<&Arg> = self
+            .args
+            .iter()
+            .filter(|a| a.kind != ArgKind::Unit)
+            .collect();
+        let mut words = words.into_iter().peekable();
+
+        for (i, arg) in args.iter().enumerate() {
+            if arg.kind == ArgKind::Words {
+                let rest: Vec<&str> = words.by_ref().collect();
+                let mut end = rest.len();
+
+                // e.g. `save` in `!luunit Backup LU600 save`
+                for later in args[i + 1..].iter().rev() {
+                    if end > 1 && later.kind.matches(rest[end - 1], units, ports) {
+                        end -= 1;
+                        invocation.args.insert(later.name, rest[end].to_string());
+                    } else if later.required {
+                        return Err(Error::Usage(self.usage(units)));
+                    }
+                }
+
+                if end > 0 {
+                    invocation.args.insert(arg.name, rest[..end].join(" "));
+                } else if arg.required {
+                    return Err(Error::Usage(self.usage(units)));
+                }
+
+                break;
+            }
+
+            match words.peek() {
+                Some(word) if arg.kind.matches(word, units, ports) => {
+                    invocation.args.insert(arg.name, word.to_string());
+                    words.next();
+                }
+                _ if arg.required => return Err(Error::Usage(self.usage(units))),
+                _ => {}
+            }
+        }
+
+        if let Some(word) = words.next() {
+            if takes_unit && invocation.unit.is_none() && units.is_multi() {
+                return Err(Error::UnitNotFound(word.to_string()));
+            }
+
+            return Err(Error::Usage(self.usage(units)));
+        }
+
+        Ok(invocation)
+    }
+}
+
+/// A parsed command
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub sender: String,
+    pub channel: String,
+    /// The unit given in chat, `None` means the active unit
+    pub unit: Option<UnitEntry>,
+    pub args: HashMap<&'static str, String>,
+}
+
+impl Invocation {
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args.get(name).map(|a| a.as_str())
+    }
+}
+
+/// When commands can be used again
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    until: Mutex<HashMap<(&'static str, Option<String>), Instant>>,
+}
+
+impl Cooldowns {
+    /// Time left before the user can use the command again
+    pub fn remaining<T>(&self, command: &CommandSpec<T>, user: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let until = self.until.lock().unwrap();
+
+        [(command.name, None), (command.name, Some(user.to_owned()))]
+            .iter()
+            .filter_map(|key| until.get(key))
+            .filter(|until| **until > now)
+            .max()
+            .map(|until| *until - now)
+    }
+
+    /// Starts the cooldown of the command for everyone and for the user
+    pub fn start<T>(&self, command: &CommandSpec<T>, user: &str) {
+        let now = Instant::now();
+        let mut until = self.until.lock().unwrap();
+
+        until.retain(|_, until| *until > now);
+
+        if command.cooldown > 0 {
+            let cooldown = Duration::from_secs(command.cooldown as u64);
+            until.insert((command.name, None), now + cooldown);
+        }
+
+        if command.user_cooldown > 0 {
+            let cooldown = Duration::from_secs(command.user_cooldown as u64);
+            until.insert((command.name, Some(user.to_owned())), now + cooldown);
+        }
+    }
+}
+
+/// All commands the bot knows
+pub struct Registry<T> {
+    commands: Vec<CommandSpec<T>>,
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Registry {
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl<T> Registry<T> {
+    pub fn register(&mut self, command: CommandSpec<T>) {
+        self.commands.push(command);
+    }
+
+    /// Applies the permissions from the config, `mod_only` raises every command to moderators
+    pub fn configure(
+        &mut self,
+        permissions: &HashMap<String, config::CommandPermission>,
+        mod_only: bool,
+    ) {
+        for command in &mut self.commands {
+            if let Some(permission) = permissions.get(command.name) {
+                if let Some(level) = permission.level {
+                    command.access.level = level;
+                }
+
+                command.access.allow = permission.allow.to_owned();
+                command.access.deny = permission.deny.to_owned();
+            }
+
+            if mod_only {
+                command.access.level = command.access.level.max(Permission::Moderator);
+            }
+        }
+    }
+
+    /// Finds the command with the trigger, e.g. `!lus`
+    pub fn find(&self, trigger: &str) -> Option<&CommandSpec<T>> {
+        self.commands
+            .iter()
+            .find(|c| c.triggers.iter().any(|t| t == trigger))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(_: Arc<()>, _: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async { Ok(String::new()) })
+    }
+
+    fn command(args: Vec<Arg>) -> CommandSpec<()> {
+        CommandSpec {
+            name: "test",
+            triggers: vec!["!test".to_string()],
+            args,
+            access: Access::new(Permission::Everyone),
+            cooldown: 0,
+            user_cooldown: 0,
+            job: JobMode::Never,
+            handler,
+        }
+    }
+
+    fn units() -> Units {
+        Units::new(vec![
+            UnitEntry {
+                alias: "cam1".to_string(),
+                id: "boss1".to_string(),
+            },
+            UnitEntry {
+                alias: "cam2".to_string(),
+                id: "boss2".to_string(),
+            },
+        ])
+    }
+
+    fn parse(
+        command: &CommandSpec<()>,
+        words: &[&str],
+        units: &Units,
+    ) -> Result<Invocation, Error> {
+        command.parse(
+            words,
+            "viewer",
+            "channel",
+            units,
+            &config::CustomUnitNames::default(),
+        )
+    }
+
+    fn badges(names: &[&str]) -> Vec<Badge> {
+        names
+            .iter()
+            .map(|name| Badge {
+                name: name.to_string(),
+                version: "1".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_unit_and_port() {
+        let command = command(vec![Arg::optional("port", ArgKind::Port), Arg::unit()]);
+
+        for words in [["CAM2", "sim1"], ["sim1", "CAM2"]] {
+            let invocation = parse(&command, &words, &units()).unwrap();
+            assert_eq!(
+                invocation.unit.as_ref().map(|u| u.id.as_str()),
+                Some("boss2")
+            );
+            assert_eq!(invocation.arg("port"), Some("sim1"));
+        }
+
+        let invocation = parse(&command, &["eth"], &units()).unwrap();
+        assert!(invocation.unit.is_none());
+        assert_eq!(invocation.arg("port"), Some("eth"));
+    }
+
+    #[test]
+    fn rejects_unknown_units() {
+        let command = command(vec![Arg::unit()]);
+
+        assert!(matches!(
+            parse(&command, &["cam3"], &units()),
+            Err(Error::UnitNotFound(alias)) if alias == "cam3"
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_words() {
+        let command = command(vec![
+            Arg::unit(),
+            Arg::required("value", ArgKind::NumberOr(vec!["low".to_string()])),
+        ]);
+
+        assert!(parse(&command, &["cam1", "LOW"], &units()).is_ok());
+        assert!(parse(&command, &["1000"], &units()).is_ok());
+
+        let usage =
+            |res| matches!(res, Err(Error::Usage(usage)) if usage == "!test [unit] <value|low>");
+        assert!(usage(parse(&command, &["cam1"], &units())));
+        assert!(usage(parse(&command, &["high"], &units())));
+        assert!(usage(parse(&command, &["cam1", "1000", "more"], &units())));
+    }
+
+    #[test]
+    fn hides_the_unit_with_a_single_unit() {
+        let command = command(vec![Arg::unit(), Arg::required("on", ArgKind::Text)]);
+        let single = Units::new(vec![UnitEntry {
+            alias: "liveu".to_string(),
+            id: "boss".to_string(),
+        }]);
+
+        assert_eq!(command.usage(&single), "!test <on>");
+        assert!(matches!(
+            parse(&command, &["a", "b"], &single),
+            Err(Error::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn permission_of_badges() {
+        let of = |names: &[&str]| Permission::of("viewer", &badges(names), None);
+
+        assert_eq!(of(&[]), Permission::Everyone);
+        assert_eq!(of(&["founder"]), Permission::Subscriber);
+        assert_eq!(of(&["subscriber", "vip"]), Permission::Vip);
+        assert_eq!(of(&["moderator", "subscriber"]), Permission::Moderator);
+        assert_eq!(of(&["broadcaster", "moderator"]), Permission::Broadcaster);
+    }
+
+    #[test]
+    fn permission_of_admin_users() {
+        let admins = ["Viewer".to_string()];
+
+        assert_eq!(
+            Permission::of("viewer", &badges(&["moderator"]), Some(&admins)),
+            Permission::Admin
+        );
+        assert_eq!(
+            Permission::of("other", &badges(&["moderator"]), Some(&admins)),
+            Permission::Moderator
+        );
+    }
+
+    #[test]
+    fn access_deny_wins() {
+        let access = Access {
+            level: Permission::Moderator,
+            allow: vec!["friend".to_string(), "troll".to_string()],
+            deny: vec!["troll".to_string()],
+        };
+
+        assert!(access.allows("Friend", Permission::Everyone));
+        assert!(!access.allows("troll", Permission::Broadcaster));
+        assert!(access.allows("mod", Permission::Moderator));
+        assert!(!access.allows("viewer", Permission::Vip));
+    }
+
+    #[test]
+    fn cooldowns_per_command_and_user() {
+        let cooldowns = Cooldowns::default();
+        let mut command = command(Vec::new());
+        command.cooldown = 5;
+        command.user_cooldown = 30;
+
+        assert_eq!(cooldowns.remaining(&command, "viewer"), None);
+
+        cooldowns.start(&command, "viewer");
+
+        let own = cooldowns.remaining(&command, "viewer").unwrap();
+        assert!(own > Duration::from_secs(25) && own <= Duration::from_secs(30));
+
+        let other = cooldowns.remaining(&command, "other").unwrap();
+        assert!(other <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn no_cooldown_when_disabled() {
+        let cooldowns = Cooldowns::default();
+        let command = command(Vec::new());
+
+        cooldowns.start(&command, "viewer");
+
+        assert_eq!(cooldowns.remaining(&command, "viewer"), None);
+    }
+}
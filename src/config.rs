@@ -1,9 +1,19 @@
 use error::Error;
 use read_input::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
-
-use crate::{error, liveu};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::IsTerminal,
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    commands::Permission,
+    error,
+    liveu::{self, LiveuApi},
+};
 
 const CONFIG_FILE_NAME: &str = "config.json";
 
@@ -13,7 +23,163 @@ pub struct Liveu {
     pub email: String,
     pub password: String,
     pub id: Option<String>,
+    #[serde(default)]
+    pub units: Option<Vec<UnitConfig>>,
     pub monitor: Monitor,
+    #[serde(default)]
+    pub http: Http,
+    #[serde(default)]
+    pub endpoints: Endpoints,
+    #[serde(default)]
+    pub delay: Delay,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoints {
+    pub api: String,
+    pub api_v2: String,
+    pub login: String,
+    pub application_id: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            api: "https://lu-central.liveu.tv/luc/luc-core-web/rest/v0".to_string(),
+            api_v2: "https://lu-central.liveu.tv/luc/luc-core-web/rest/v2".to_string(),
+            login: "https://solo-api.liveu.tv/v1_prod/zendesk/userlogin".to_string(),
+            application_id: "SlZ3SHqiqtYJRkF0zO".to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Overrides the endpoints with the ones set in the environment
+    pub fn apply_env(&mut self) {
+        let vars = [
+            ("LIVEU_API", &mut self.api),
+            ("LIVEU_API_V2", &mut self.api_v2),
+            ("LIVEU_LOGIN_URL", &mut self.login),
+            ("LIVEU_APPLICATION_ID", &mut self.application_id),
+        ];
+
+        for (key, value) in vars {
+            if let Ok(var) = std::env::var(key) {
+                *value = var;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UnitConfig {
+    /// Short name used in chat commands, e.g. `cam1`
+    pub alias: String,
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Http {
+    /// Seconds to wait for a connection to LiveU Central
+    pub connect_timeout: u64,
+    /// Seconds to wait for a single request to complete
+    pub request_timeout: u64,
+    /// Seconds a call may take in total, including a token refresh
+    pub total_timeout: u64,
+    /// Seconds an idle pooled connection is kept alive
+    pub pool_idle_timeout: u64,
+    #[serde(default)]
+    pub retry: Retry,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Retry {
+    /// How many times a single call may be retried
+    pub max_retries: u32,
+    /// Milliseconds to wait before the first retry, doubled on every attempt
+    pub base_delay_ms: u64,
+    /// Upper limit in milliseconds for the wait between retries
+    pub max_delay_ms: u64,
+    /// Also retry calls that change the unit, like starting the stream
+    pub retry_mutating: bool,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 8000,
+            retry_mutating: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Delay {
+    /// Lowest delay in milliseconds that can be set from chat
+    pub min: u64,
+    /// Highest delay in milliseconds that can be set from chat
+    pub max: u64,
+    /// Named delays, e.g. `{"low": 1000}` for `!ludelay low`
+    pub presets: BTreeMap<String, u64>,
+    /// Stop the stream while changing the delay and start it again afterwards
+    pub restart_stream: bool,
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Delay {
+            min: 500,
+            max: 10000,
+            presets: BTreeMap::from([("low".to_string(), 1000), ("high".to_string(), 5000)]),
+            restart_stream: true,
+        }
+    }
+}
+
+impl Delay {
+    /// Gets the delay of a preset or a value in milliseconds
+    pub fn parse(&self, value: &str) -> Option<u64> {
+        self.presets
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map(|(_, delay)| *delay)
+            .or_else(|| value.parse().ok())
+    }
+
+    /// Gets the name of the preset with the delay
+    pub fn preset_name(&self, delay: u64) -> Option<&str> {
+        self.presets
+            .iter()
+            .find(|(_, d)| **d == delay)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl Monitor {
+    /// Time between polls of the unit status, at least a second
+    pub fn poll_interval(&self) -> Duration {
+        let secs = self.poll_interval.unwrap_or_else(default_poll_interval);
+        Duration::from_secs(secs.max(1))
+    }
+}
+
+impl Default for Http {
+    fn default() -> Self {
+        Http {
+            connect_timeout: 5,
+            request_timeout: 10,
+            total_timeout: 30,
+            pool_idle_timeout: 90,
+            retry: Retry::default(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,9 +188,127 @@ pub struct Monitor {
     pub battery: bool,
     pub battery_charging: bool,
     pub battery_notification: Vec<u8>,
-    pub battery_interval: u64,
+    /// Percentage the battery has to charge above a notification before it's sent again
+    #[serde(default = "default_battery_hysteresis")]
+    pub battery_hysteresis: u8,
+    /// Minutes of battery left to notify at, e.g. `[20, 10]`
+    #[serde(default)]
+    pub battery_runtime_notification: Vec<u32>,
     pub modems: bool,
-    pub modems_interval: u64,
+    /// Seconds a modem has to be gone before it's reported as disconnected
+    #[serde(default)]
+    pub modem_grace_period: u64,
+    /// Drops within `modem_flap_window` before a modem is reported as unstable
+    #[serde(default)]
+    pub modem_flap_drops: Option<usize>,
+    /// Seconds drops of a modem are counted for
+    #[serde(default = "default_modem_flap_window")]
+    pub modem_flap_window: u64,
+    /// Messages when the unit starts or stops streaming and goes offline, disabled when not set
+    #[serde(default)]
+    pub lifecycle: Option<Lifecycle>,
+    /// Alerts when the bitrate stays low, disabled when not set
+    #[serde(default)]
+    pub bitrate: Option<BitrateAlert>,
+    /// Seconds between polls of the unit status, see `Monitor::poll_interval`
+    #[serde(default)]
+    pub poll_interval: Option<u64>,
+    /// Replaced by `poll_interval`, still read from older configs
+    #[serde(default, skip_serializing)]
+    pub battery_interval: Option<u64>,
+    /// Replaced by `poll_interval`, still read from older configs
+    #[serde(default, skip_serializing)]
+    pub modems_interval: Option<u64>,
+    /// Seconds a polled status may be used by chat before it's fetched again
+    #[serde(default = "default_max_snapshot_age")]
+    pub max_snapshot_age: u64,
+    /// Longest wait in seconds between polls while LiveU Central can't be reached
+    #[serde(default = "default_max_poll_backoff")]
+    pub max_poll_backoff: u64,
+    /// Failed polls in a row before chat is told LiveU Central can't be reached
+    #[serde(default)]
+    pub unreachable_alert: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BitrateAlert {
+    /// Kbps the total LRT has to stay below to alert
+    #[serde(default)]
+    pub total_below: Option<u32>,
+    /// Kbps a single modem has to stay below to alert
+    #[serde(default)]
+    pub modem_below: Option<u32>,
+    /// Seconds the bitrate has to stay low before alerting
+    #[serde(default = "default_bitrate_alert_duration")]
+    pub duration: u64,
+}
+
+/// Messages for changes of the unit state, `{duration}` is how long the previous state lasted.
+/// Set a message to `null` to not send it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Lifecycle {
+    #[serde(default = "default_started_message")]
+    pub started: Option<String>,
+    #[serde(default = "default_stopped_message")]
+    pub stopped: Option<String>,
+    #[serde(default = "default_offline_message")]
+    pub offline: Option<String>,
+    #[serde(default = "default_offline_while_streaming_message")]
+    pub offline_while_streaming: Option<String>,
+    #[serde(default = "default_online_message")]
+    pub online: Option<String>,
+    #[serde(default = "default_no_camera_message")]
+    pub no_camera: Option<String>,
+}
+
+fn default_started_message() -> Option<String> {
+    Some("LiveU: Stream started".to_string())
+}
+
+fn default_stopped_message() -> Option<String> {
+    Some("LiveU: Stream stopped after {duration}".to_string())
+}
+
+fn default_offline_message() -> Option<String> {
+    Some("LiveU went offline".to_string())
+}
+
+fn default_offline_while_streaming_message() -> Option<String> {
+    Some("LiveU went offline while streaming".to_string())
+}
+
+fn default_online_message() -> Option<String> {
+    Some("LiveU back online after {duration}".to_string())
+}
+
+fn default_no_camera_message() -> Option<String> {
+    Some("LiveU: No camera connected".to_string())
+}
+
+fn default_modem_flap_window() -> u64 {
+    120
+}
+
+fn default_bitrate_alert_duration() -> u64 {
+    10
+}
+
+fn default_battery_hysteresis() -> u8 {
+    3
+}
+
+fn default_poll_interval() -> u64 {
+    10
+}
+
+fn default_max_poll_backoff() -> u64 {
+    300
+}
+
+fn default_max_snapshot_age() -> u64 {
+    15
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -41,6 +325,28 @@ pub struct Twitch {
 #[serde(rename_all = "camelCase")]
 pub struct Commands {
     pub cooldown: u16,
+    /// Cooldowns of single commands by name, e.g. `{"battery": 10, "stop": 0}`
+    #[serde(default)]
+    pub cooldowns: HashMap<String, u16>,
+    /// Seconds before the same user can use a command again, 0 to disable
+    #[serde(default)]
+    pub user_cooldown: u16,
+    /// The broadcaster and admin users ignore cooldowns
+    #[serde(default = "default_cooldown_bypass")]
+    pub cooldown_bypass: bool,
+    /// Who may use which command by command name, e.g. `{"stop": {"level": "moderator"}}`
+    #[serde(default)]
+    pub permissions: HashMap<String, CommandPermission>,
+    /// Commands by name that have to be confirmed, e.g. `["reboot", "stop"]`
+    #[serde(default)]
+    pub confirm: Vec<String>,
+    /// Seconds to confirm a command
+    #[serde(default = "default_confirm_timeout")]
+    pub confirm_timeout: u64,
+    /// Reply when a command is on cooldown, `{command}` and `{seconds}` are replaced.
+    /// Commands on cooldown are ignored when not set.
+    #[serde(default)]
+    pub cooldown_message: Option<String>,
     pub stats: Vec<String>,
     pub battery: Vec<String>,
     pub start: String,
@@ -48,6 +354,68 @@ pub struct Commands {
     pub restart: String,
     pub reboot: String,
     pub delay: String,
+    #[serde(default = "default_status_command")]
+    pub status: String,
+    #[serde(default = "default_unit_command")]
+    pub unit: String,
+    #[serde(default = "default_modem_command")]
+    pub modem: String,
+    #[serde(default = "default_jobs_command")]
+    pub jobs: String,
+    #[serde(default = "default_cancel_command")]
+    pub cancel: String,
+    /// Milliseconds the stats command waits for all its data
+    #[serde(default = "default_stats_deadline")]
+    pub stats_deadline_ms: u64,
+    /// Shows the signal quality of every modem in the stats command
+    #[serde(default)]
+    pub signal_bars: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandPermission {
+    /// Lowest role that may use the command
+    #[serde(default)]
+    pub level: Option<Permission>,
+    /// Usernames that may always use the command
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Usernames that may never use the command
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+fn default_confirm_timeout() -> u64 {
+    15
+}
+
+fn default_cooldown_bypass() -> bool {
+    true
+}
+
+fn default_stats_deadline() -> u64 {
+    3000
+}
+
+fn default_status_command() -> String {
+    "!lustatus".to_string()
+}
+
+fn default_unit_command() -> String {
+    "!luunit".to_string()
+}
+
+fn default_modem_command() -> String {
+    "!lumodem".to_string()
+}
+
+fn default_jobs_command() -> String {
+    "!lujobs".to_string()
+}
+
+fn default_cancel_command() -> String {
+    "!lucancel".to_string()
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -76,10 +444,67 @@ impl Config {
         let file = fs::read_to_string(path)?;
         let mut config = serde_json::from_str::<Config>(&file)?;
         Self::lowercase_settings(&mut config);
+        config.liveu.endpoints.apply_env();
+
+        if Self::upgrade_settings(&mut config) {
+            println!(
+                "Config: batteryInterval and modemsInterval are replaced by pollInterval, polling every {}s",
+                config.liveu.monitor.poll_interval().as_secs()
+            );
+        }
 
         Ok(config)
     }
 
+    /// Saves the boss id of a unit to the config on disk. Sets `id` when no alias
+    /// is given, otherwise the unit with the alias in `units`.
+    pub fn save_unit_id(alias: Option<&str>, id: &str) -> Result<(), Error> {
+        let file = fs::read_to_string(CONFIG_FILE_NAME)?;
+        let mut config = serde_json::from_str::<Config>(&file)?;
+        Self::upgrade_settings(&mut config);
+
+        match alias {
+            Some(alias) => {
+                let unit = config
+                    .liveu
+                    .units
+                    .iter_mut()
+                    .flatten()
+                    .find(|unit| unit.alias.eq_ignore_ascii_case(alias))
+                    .ok_or_else(|| Error::UnitNotFound(alias.to_owned()))?;
+
+                unit.id = id.to_owned();
+            }
+            None => config.liveu.id = Some(id.to_owned()),
+        }
+
+        fs::write(CONFIG_FILE_NAME, serde_json::to_string_pretty(&config)?)?;
+
+        Ok(())
+    }
+
+    /// Moves settings of older versions to their replacement, returns true if there were any
+    pub fn upgrade_settings(config: &mut Config) -> bool {
+        let monitor = &mut config.liveu.monitor;
+        let interval = [
+            monitor.battery_interval.take(),
+            monitor.modems_interval.take(),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        match interval {
+            Some(interval) => {
+                // an explicit pollInterval wins over the old intervals
+                monitor.poll_interval.get_or_insert(interval);
+
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Lowercase settings which should always be lowercase
     pub fn lowercase_settings(config: &mut Config) {
         let Twitch {
@@ -99,10 +524,20 @@ impl Config {
                 *user = user.to_lowercase();
             }
         }
+
+        if let Some(units) = &mut config.liveu.units {
+            for unit in units {
+                unit.alias = unit.alias.to_lowercase();
+            }
+        }
     }
 
     /// Asks the user to enter settings and save it to disk
     pub async fn ask_for_settings() -> Result<Self, Error> {
+        if !std::io::stdin().is_terminal() {
+            return Err(Error::NotInteractive);
+        }
+
         println!("Please enter your Liveu details below");
 
         let email = input().msg("Email: ").get();
@@ -117,9 +552,20 @@ impl Config {
         let monitor = Monitor {
             battery: monitor_enabled,
             battery_notification: [99, 50, 10, 5, 1].to_vec(),
+            battery_hysteresis: default_battery_hysteresis(),
+            battery_runtime_notification: Vec::new(),
             modems: monitor_enabled,
-            battery_interval: 10,
-            modems_interval: 10,
+            modem_grace_period: 0,
+            modem_flap_drops: None,
+            modem_flap_window: default_modem_flap_window(),
+            lifecycle: None,
+            bitrate: None,
+            poll_interval: Some(default_poll_interval()),
+            battery_interval: None,
+            modems_interval: None,
+            max_snapshot_age: default_max_snapshot_age(),
+            max_poll_backoff: default_max_poll_backoff(),
+            unreachable_alert: None,
             battery_charging: monitor_enabled,
         };
 
@@ -127,10 +573,16 @@ impl Config {
             email,
             password,
             id: None,
+            units: None,
             monitor,
+            http: Http::default(),
+            endpoints: Endpoints::default(),
+            delay: Delay::default(),
         };
 
-        let lauth = liveu::Liveu::authenticate(liveu.clone()).await?;
+        let mut auth_config = liveu.clone();
+        auth_config.endpoints.apply_env();
+        let lauth = liveu::Liveu::authenticate(auth_config).await?;
         let inventories = lauth.get_inventories().await?;
 
         if inventories.units.len() > 1 {
@@ -144,7 +596,7 @@ impl Config {
             );
 
             if option {
-                let loc = liveu::Liveu::get_boss_id_location(&inventories);
+                let loc = liveu::Liveu::get_boss_id_location(&inventories)?;
                 liveu.id = Some(inventories.units[loc].id.to_owned());
             }
         }
@@ -173,6 +625,13 @@ impl Config {
                 .err("Please enter a number")
                 .default(5)
                 .get(),
+            cooldowns: HashMap::new(),
+            user_cooldown: 0,
+            cooldown_bypass: default_cooldown_bypass(),
+            permissions: HashMap::new(),
+            confirm: Vec::new(),
+            confirm_timeout: default_confirm_timeout(),
+            cooldown_message: None,
             stats: vec![
                 "!lustats".to_string(),
                 "!liveustats".to_string(),
@@ -188,6 +647,13 @@ impl Config {
             restart: "!lurestart".to_string(),
             reboot: "!lureboot".to_string(),
             delay: "!ludelay".to_string(),
+            status: default_status_command(),
+            unit: default_unit_command(),
+            modem: default_modem_command(),
+            jobs: default_jobs_command(),
+            cancel: default_cancel_command(),
+            stats_deadline_ms: default_stats_deadline(),
+            signal_bars: false,
         };
 
         let q: String = input()
@@ -252,6 +718,7 @@ impl Config {
         );
 
         Self::lowercase_settings(&mut config);
+        config.liveu.endpoints.apply_env();
 
         Ok(config)
     }
@@ -280,6 +747,29 @@ impl Default for CustomUnitNames {
     }
 }
 
+impl CustomUnitNames {
+    /// Gets the port LiveU uses for a custom or default port name
+    pub fn raw_port(&self, name: &str) -> Option<&'static str> {
+        let default = CustomUnitNames::default();
+
+        let ports = [
+            (&self.ethernet, &default.ethernet, "eth0"),
+            (&self.wifi, &default.wifi, "wlan0"),
+            (&self.sim1, &default.sim1, "0"),
+            (&self.sim2, &default.sim2, "1"),
+            (&self.usb1, &default.usb1, "2"),
+            (&self.usb2, &default.usb2, "3"),
+        ];
+
+        ports
+            .iter()
+            .find(|(custom, default, _)| {
+                custom.eq_ignore_ascii_case(name) || default.eq_ignore_ascii_case(name)
+            })
+            .map(|(_, _, port)| *port)
+    }
+}
+
 /// Converts y or n to bool.
 fn input_to_bool(confirm: &str) -> bool {
     if confirm == "y" {
@@ -288,3 +778,92 @@ fn input_to_bool(confirm: &str) -> bool {
 
     false
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A config with only the required settings, shared with the tests of other modules
+    pub(crate) fn config() -> Config {
+        with_monitor("")
+    }
+
+    fn with_monitor(monitor: &str) -> Config {
+        let json = format!(
+            r#"{{
+                "liveu": {{
+                    "email": "", "password": "", "id": null,
+                    "monitor": {{
+                        "battery": true, "batteryCharging": true, "batteryNotification": [50],
+                        "modems": true{}
+                    }}
+                }},
+                "twitch": {{ "botUsername": "", "botOauth": "", "channel": "", "adminUsers": null, "modOnly": false }},
+                "commands": {{
+                    "cooldown": 5, "stats": ["!lus"], "battery": ["!lub"], "start": "!lustart",
+                    "stop": "!lustop", "restart": "!lurestart", "reboot": "!lureboot", "delay": "!ludelay"
+                }},
+                "rtmp": null,
+                "customPortNames": null
+            }}"#,
+            monitor
+        );
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn delay_presets_and_numbers() {
+        let delay = Delay::default();
+
+        assert_eq!(delay.parse("LOW"), Some(1000));
+        assert_eq!(delay.parse("2500"), Some(2500));
+        assert_eq!(delay.parse("medium"), None);
+        assert_eq!(delay.parse("-5"), None);
+        assert_eq!(delay.preset_name(5000), Some("high"));
+        assert_eq!(delay.preset_name(2500), None);
+    }
+
+    #[test]
+    fn old_intervals_become_the_poll_interval() {
+        let mut config = with_monitor(r#", "batteryInterval": 30, "modemsInterval": 5"#);
+
+        assert!(Config::upgrade_settings(&mut config));
+        assert_eq!(config.liveu.monitor.poll_interval, Some(5));
+
+        let saved = serde_json::to_string(&config).unwrap();
+        assert!(saved.contains(r#""pollInterval":5"#));
+        assert!(!saved.contains("modemsInterval"));
+    }
+
+    #[test]
+    fn poll_interval_wins_over_old_intervals() {
+        for poll_interval in [20, default_poll_interval()] {
+            let monitor = format!(
+                r#", "pollInterval": {}, "modemsInterval": 5"#,
+                poll_interval
+            );
+            let mut config = with_monitor(&monitor);
+
+            assert!(Config::upgrade_settings(&mut config));
+            assert_eq!(
+                config.liveu.monitor.poll_interval(),
+                Duration::from_secs(poll_interval)
+            );
+        }
+
+        let mut config = with_monitor(r#""#);
+        assert!(!Config::upgrade_settings(&mut config));
+        assert_eq!(
+            config.liveu.monitor.poll_interval(),
+            Duration::from_secs(default_poll_interval())
+        );
+    }
+
+    #[test]
+    fn poll_interval_is_at_least_a_second() {
+        let config = with_monitor(r#", "pollInterval": 0"#);
+
+        assert_eq!(config.liveu.monitor.poll_interval(), Duration::from_secs(1));
+    }
+}
@@ -8,7 +8,10 @@ pub enum Error {
     Write(#[from] std::io::Error),
 
     #[error("Request failed: {0}")]
-    RequestFailed(#[from] reqwest::Error),
+    RequestFailed(reqwest::Error),
+
+    #[error("LiveU API timed out")]
+    Timeout,
 
     #[error("Invalid credentials can't login")]
     InvalidCredentials,
@@ -25,9 +28,40 @@ pub enum Error {
     #[error("No units found")]
     NoUnitsFound,
 
+    #[error("No unit found with the id, name or registration code {0}")]
+    UnitNotFound(String),
+
+    #[error("Found {0} units, set the id of the unit to use in config.json")]
+    UnitSelectionRequired(usize),
+
+    #[error("No usable config.json found and stdin is not a terminal to ask for settings")]
+    NotInteractive,
+
     #[error("Status not available")]
     StatusNotAvailable,
 
+    #[error("LiveU API unavailable ({0})")]
+    ApiUnavailable(reqwest::StatusCode),
+
+    #[error("LiveU API answered {0}")]
+    UnexpectedStatus(reqwest::StatusCode),
+
+    #[error("LiveU busy with {0} (#{1})")]
+    JobRunning(String, u64),
+
+    #[error("Usage: {0}")]
+    Usage(String),
+
     #[error("Not enough permissions to use command")]
     NotEnoughPermissions,
 }
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return Error::Timeout;
+        }
+
+        Error::RequestFailed(error)
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::task::AbortHandle;
+
+use crate::{error::Error, units::UnitEntry};
+
+/// A command running in the background
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: u64,
+    pub command: &'static str,
+    pub unit: UnitEntry,
+    pub user: String,
+    pub started: Instant,
+    abort: Arc<AbortHandle>,
+}
+
+impl Job {
+    pub fn running_for(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+#[derive(Debug, Default)]
+struct JobsInner {
+    next_id: u64,
+    jobs: Vec<Job>,
+}
+
+/// Removes the job once its task is done, even when it panicked
+struct Finished {
+    jobs: Jobs,
+    id: u64,
+}
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        self.jobs.remove(self.id);
+    }
+}
+
+/// Commands that change a unit, only one of them can run on a unit at a time
+#[derive(Debug, Clone, Default)]
+pub struct Jobs {
+    inner: Arc<Mutex<JobsInner>>,
+}
+
+impl Jobs {
+    /// Runs the future in the background unless another job is running on the unit
+    pub fn start<F>(
+        &self,
+        command: &'static str,
+        unit: UnitEntry,
+        user: &str,
+        future: F,
+    ) -> Result<u64, Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(running) = inner.jobs.iter().find(|j| j.unit.id == unit.id) {
+            return Err(Error::JobRunning(running.command.to_string(), running.id));
+        }
+
+        inner.next_id += 1;
+        let id = inner.next_id;
+
+        let finished = Finished {
+            jobs: self.clone(),
+            id,
+        };
+        let handle = tokio::spawn(async move {
+            let _finished = finished;
+            future.await;
+        });
+
+        inner.jobs.push(Job {
+            id,
+            command,
+            unit,
+            user: user.to_owned(),
+            started: Instant::now(),
+            abort: Arc::new(handle.abort_handle()),
+        });
+
+        Ok(id)
+    }
+
+    pub fn all(&self) -> Vec<Job> {
+        self.inner.lock().unwrap().jobs.clone()
+    }
+
+    /// Stops the job, returns `None` if it's not running
+    pub fn cancel(&self, id: u64) -> Option<Job> {
+        let job = self.remove(id)?;
+        job.abort.abort();
+
+        Some(job)
+    }
+
+    fn remove(&self, id: u64) -> Option<Job> {
+        let mut inner = self.inner.lock().unwrap();
+        let pos = inner.jobs.iter().position(|j| j.id == id)?;
+
+        Some(inner.jobs.remove(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    fn unit(id: &str) -> UnitEntry {
+        UnitEntry {
+            alias: id.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn one_job_per_unit() {
+        let jobs = Jobs::default();
+        let (tx, rx) = oneshot::channel::<()>();
+
+        let id = jobs
+            .start("reboot", unit("boss1"), "mod", async {
+                let _ = rx.await;
+            })
+            .unwrap();
+
+        assert!(matches!(
+            jobs.start("start", unit("boss1"), "mod", async {}),
+            Err(Error::JobRunning(command, running)) if command == "reboot" && running == id
+        ));
+
+        let (other_tx, other_rx) = oneshot::channel::<()>();
+        jobs.start("stop", unit("boss2"), "mod", async {
+            let _ = other_rx.await;
+        })
+        .unwrap();
+        assert_eq!(jobs.all().len(), 2);
+
+        tx.send(()).unwrap();
+        other_tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(jobs.all().is_empty());
+        assert!(jobs.start("start", unit("boss1"), "mod", async {}).is_ok());
+    }
+
+    #[tokio::test]
+    async fn cancel_frees_the_unit() {
+        let jobs = Jobs::default();
+
+        let id = jobs
+            .start("reboot", unit("boss1"), "mod", std::future::pending())
+            .unwrap();
+
+        assert_eq!(jobs.cancel(id).map(|job| job.command), Some("reboot"));
+        assert!(jobs.cancel(id).is_none());
+        assert!(jobs.all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn panicking_job_frees_the_unit() {
+        let jobs = Jobs::default();
+
+        jobs.start("reboot", unit("boss1"), "mod", async {
+            panic!("handler crashed");
+        })
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(jobs.all().is_empty());
+    }
+}
@@ -1,6 +1,27 @@
+use std::time::Duration;
+
+pub mod commands;
 pub mod config;
 pub mod error;
+pub mod jobs;
 pub mod liveu;
 pub mod liveu_monitor;
 pub mod nginx;
+pub mod poller;
+pub mod supervisor;
 pub mod twitch;
+pub mod units;
+
+/// Formats a duration like 1h 5m 3s
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours != 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes != 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -2,21 +2,23 @@ use crate::{
     config::{self, Liveu as Config_liveu},
     error::Error,
 };
+use async_trait::async_trait;
 use read_input::prelude::*;
 use reqwest::{
-    header::{ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_TYPE},
+    header::{ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
     Method, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::Mutex;
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, RwLock};
 use uuid::Uuid;
 
-const APPLICATION_ID: &str = "SlZ3SHqiqtYJRkF0zO";
-const LIVEU_API: &str = "https://lu-central.liveu.tv/luc/luc-core-web/rest/v0";
-const LIVEU_API_V2: &str = "https://lu-central.liveu.tv/luc/luc-core-web/rest/v2";
-
 #[derive(Deserialize)]
 struct Res {
     data: Data,
@@ -33,12 +35,46 @@ struct AuthRes {
     expires_in: u64,
 }
 
+#[derive(Debug, Clone)]
+struct Token {
+    value: String,
+    obtained_at: Instant,
+    expires_at: Instant,
+}
+
+impl Token {
+    /// Shortest time between two refreshes, so short-lived tokens don't cause a login loop
+    const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// When the token should be refreshed, some time before it actually expires
+    fn refresh_at(&self) -> Instant {
+        let lifetime = self.expires_at.duration_since(self.obtained_at);
+        let margin = (lifetime / 10)
+            .max(Duration::from_secs(60))
+            .min(lifetime / 2);
+
+        self.obtained_at + (lifetime - margin).max(Self::MIN_REFRESH_INTERVAL)
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    token: RwLock<Token>,
+    refresh: Mutex<()>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenStatus {
+    pub age: Duration,
+    pub expires_in: Duration,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct UnitInterfaces {
     pub interfaces: Vec<Interface>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Interface {
     pub connected: bool,
@@ -56,19 +92,62 @@ pub struct Interface {
     pub signal_quality: u32,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Unit {
     pub id: String,
     pub reg_code: String,
     pub status: String,
     pub name: String,
+    #[serde(default)]
+    pub inventory_id: String,
+    #[serde(default)]
+    pub inventory_name: String,
 }
 
+impl Unit {
+    /// The unit name followed by the inventory it belongs to
+    pub fn display_name(&self) -> String {
+        if self.inventory_name.is_empty() {
+            return self.name.to_owned();
+        }
+
+        format!("{} [{}]", self.name, self.inventory_name)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Inventory {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    units: Vec<Unit>,
+}
+
+/// The units of every inventory merged together
 #[derive(Deserialize, Debug)]
 pub struct Inventories {
     pub units: Vec<Unit>,
 }
 
+impl Inventories {
+    /// Finds a unit by its boss id, name or registration code
+    pub fn find_unit(&self, query: &str) -> Option<&Unit> {
+        self.units.iter().find(|unit| {
+            unit.id == query
+                || unit.name.eq_ignore_ascii_case(query)
+                || unit.reg_code.eq_ignore_ascii_case(query)
+        })
+    }
+
+    /// Same as `find_unit` but errors when there is no such unit
+    pub fn resolve_unit(&self, query: &str) -> Result<&Unit, Error> {
+        self.find_unit(query)
+            .ok_or_else(|| Error::UnitNotFound(query.to_owned()))
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 pub struct Battery {
@@ -79,13 +158,76 @@ pub struct Battery {
     pub charging: bool,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Video {
     pub resolution: Option<String>,
     pub bitrate: Option<u32>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceReq {
+    pub interface: InterfaceEnabled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceEnabled {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnitState {
+    Offline,
+    OnlineNoCamera,
+    Idle,
+    Streaming,
+    /// The state could not be determined, e.g. because LiveU could not be reached
+    Unknown(String),
+}
+
+impl UnitState {
+    /// Derives the state from the unit status, video and connected interfaces
+    pub fn from_parts(
+        status: Option<&str>,
+        video: &Result<Video, Error>,
+        interfaces: Option<&[Interface]>,
+    ) -> Self {
+        if status.is_some_and(|status| status.eq_ignore_ascii_case("offline")) {
+            return UnitState::Offline;
+        }
+
+        if interfaces.is_some_and(|interfaces| interfaces.is_empty()) {
+            return UnitState::Offline;
+        }
+
+        match video {
+            Ok(Video {
+                resolution: None, ..
+            }) => UnitState::OnlineNoCamera,
+            Ok(Video {
+                bitrate: Some(_), ..
+            }) => UnitState::Streaming,
+            Ok(_) => UnitState::Idle,
+            Err(Error::StatusNotAvailable) => UnitState::Offline,
+            Err(e) => UnitState::Unknown(e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for UnitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnitState::Offline => write!(f, "offline"),
+            UnitState::OnlineNoCamera => write!(f, "online without camera"),
+            UnitState::Idle => write!(f, "idle"),
+            UnitState::Streaming => write!(f, "streaming"),
+            UnitState::Unknown(e) => write!(f, "unknown ({})", e),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DelayReq {
@@ -100,30 +242,53 @@ pub struct Delay {
 
 #[derive(Debug, Clone)]
 pub struct Liveu {
-    access_token: Arc<Mutex<String>>,
+    client: reqwest::Client,
+    session: Arc<Session>,
     config: Config_liveu,
 }
 
 impl Liveu {
     pub async fn authenticate(config: Config_liveu) -> Result<Self, Error> {
-        let token = if let Ok(token) = Self::get_access_token(&config).await {
-            token
-        } else {
-            return Err(Error::InvalidCredentials);
+        let client = Self::build_client(&config.http)?;
+
+        let token = match Self::get_access_token(&client, &config).await {
+            Ok(token) => token,
+            Err(Error::Timeout) => return Err(Error::Timeout),
+            Err(_) => return Err(Error::InvalidCredentials),
         };
 
-        Ok(Liveu {
-            access_token: Arc::new(Mutex::new(token)),
+        let liveu = Liveu {
+            client,
+            session: Arc::new(Session {
+                token: RwLock::new(token),
+                refresh: Mutex::new(()),
+            }),
             config,
-        })
+        };
+
+        liveu.spawn_token_refresher();
+
+        Ok(liveu)
     }
 
-    async fn get_access_token(config: &Config_liveu) -> Result<String, Error> {
+    /// Builds the client that is shared by every request to LiveU
+    fn build_client(http: &config::Http) -> Result<reqwest::Client, Error> {
+        Ok(reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(http.connect_timeout))
+            .timeout(Duration::from_secs(http.request_timeout))
+            .pool_idle_timeout(Duration::from_secs(http.pool_idle_timeout))
+            .build()?)
+    }
+
+    async fn get_access_token(
+        client: &reqwest::Client,
+        config: &Config_liveu,
+    ) -> Result<Token, Error> {
         let user_session = Uuid::new_v4();
-        let client = reqwest::Client::new();
+        let obtained_at = Instant::now();
 
         let res = client
-            .post("https://solo-api.liveu.tv/v1_prod/zendesk/userlogin")
+            .post(&config.endpoints.login)
             .basic_auth(&config.email, Some(&config.password))
             .header(ACCEPT, "application/json, text/plain, */*")
             .header(ACCEPT_LANGUAGE, "en-US,en;q=0.9")
@@ -138,25 +303,134 @@ impl Liveu {
             .json::<Res>()
             .await?;
 
-        Ok(res.data.response.access_token)
+        let AuthRes {
+            access_token,
+            expires_in,
+        } = res.data.response;
+
+        Ok(Token {
+            value: access_token,
+            obtained_at,
+            expires_at: obtained_at + Duration::from_secs(expires_in),
+        })
     }
 
-    /// Sends the specified request. Gets a new token if unauthorized.
+    /// Gets a new token unless someone else already did since `seen` was obtained.
+    /// Concurrent callers wait for the same login instead of each logging in.
+    async fn refresh_token(&self, seen: Instant) -> Result<(), Error> {
+        let _guard = self.session.refresh.lock().await;
+
+        if self.session.token.read().await.obtained_at != seen {
+            return Ok(());
+        }
+
+        let token = Self::get_access_token(&self.client, &self.config).await?;
+        *self.session.token.write().await = token;
+
+        Ok(())
+    }
+
+    /// Refreshes the token in the background before it expires
+    fn spawn_token_refresher(&self) {
+        let session = Arc::downgrade(&self.session);
+        let client = self.client.clone();
+        let config = self.config.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let refresh_at = match session.upgrade() {
+                    Some(session) => session.token.read().await.refresh_at(),
+                    None => return,
+                };
+
+                tokio::time::sleep_until(refresh_at.into()).await;
+
+                let liveu = match session.upgrade() {
+                    Some(session) => Liveu {
+                        client: client.clone(),
+                        session,
+                        config: config.clone(),
+                    },
+                    None => return,
+                };
+
+                let seen = liveu.session.token.read().await.obtained_at;
+
+                if liveu.refresh_token(seen).await.is_err() {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+            }
+        });
+    }
+
+    /// How old the current token is and how long until it expires
+    pub async fn token_status(&self) -> TokenStatus {
+        let token = self.session.token.read().await;
+        let now = Instant::now();
+
+        TokenStatus {
+            age: now.duration_since(token.obtained_at),
+            expires_in: token.expires_at.saturating_duration_since(now),
+        }
+    }
+
+    /// Sends the specified request. Retries transient failures when the method allows it.
     pub async fn send_request<T: Serialize + Clone>(
         &self,
         method: Method,
         url: &str,
         payload: Option<T>,
     ) -> Result<reqwest::Response, Error> {
+        let total_timeout = Duration::from_secs(self.config.http.total_timeout);
+        let retry = &self.config.http.retry;
+        let can_retry = Self::is_idempotent(&method) || retry.retry_mutating;
+
+        let deadline = Instant::now() + total_timeout;
+
+        tokio::time::timeout(total_timeout, async {
+            let mut attempt = 0;
+
+            loop {
+                let res = self
+                    .send_authenticated_request(method.clone(), url, payload.clone())
+                    .await;
+
+                if !can_retry || attempt >= retry.max_retries {
+                    return res;
+                }
+
+                let delay = match Self::retry_delay(&res, attempt, retry) {
+                    Some(delay) => delay,
+                    None => return res,
+                };
+
+                // the retry would not finish in time, the last answer is better than a timeout
+                if Instant::now() + delay >= deadline {
+                    return res;
+                }
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        })
+        .await
+        .map_err(|_| Error::Timeout)?
+    }
+
+    /// Sends the specified request. Gets a new token if unauthorized.
+    async fn send_authenticated_request<T: Serialize + Clone>(
+        &self,
+        method: Method,
+        url: &str,
+        payload: Option<T>,
+    ) -> Result<reqwest::Response, Error> {
+        let seen = self.session.token.read().await.obtained_at;
         let mut res = self
             .try_send_request(method.clone(), url, payload.clone())
             .await?;
 
         if res.status() == 401 {
-            {
-                let mut token = self.access_token.lock().await;
-                *token = Self::get_access_token(&self.config).await?;
-            }
+            self.refresh_token(seen).await?;
 
             res = self
                 .try_send_request(method.clone(), url, payload.clone())
@@ -166,23 +440,83 @@ impl Liveu {
         Ok(res)
     }
 
+    /// LiveU Central itself failed, says nothing about whether the unit is online
+    fn is_unavailable(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+
+    /// The error for an answer a request did not expect, only 204 and 404 mean the unit is offline
+    fn status_error(status: StatusCode) -> Error {
+        match status {
+            StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Error::StatusNotAvailable,
+            status if Self::is_unavailable(status) => Error::ApiUnavailable(status),
+            status => Error::UnexpectedStatus(status),
+        }
+    }
+
+    fn is_idempotent(method: &Method) -> bool {
+        method == Method::GET || method == Method::HEAD
+    }
+
+    /// Returns how long to wait before retrying, or `None` if the result should not be retried
+    fn retry_delay(
+        res: &Result<reqwest::Response, Error>,
+        attempt: u32,
+        retry: &config::Retry,
+    ) -> Option<Duration> {
+        match res {
+            Ok(res) => {
+                let status = res.status();
+                let max_delay = Duration::from_millis(retry.max_delay_ms);
+
+                if !Self::is_unavailable(status) {
+                    return None;
+                }
+
+                let retry_after = res
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|secs| Duration::from_secs(secs).min(max_delay));
+
+                Some(retry_after.unwrap_or_else(|| Self::backoff(attempt, retry)))
+            }
+            Err(Error::Timeout) => Some(Self::backoff(attempt, retry)),
+            Err(Error::RequestFailed(e)) if e.is_connect() || e.is_request() => {
+                Some(Self::backoff(attempt, retry))
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Exponential backoff with jitter, somewhere between half and the full delay
+    fn backoff(attempt: u32, retry: &config::Retry) -> Duration {
+        let delay = retry
+            .base_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt))
+            .min(retry.max_delay_ms);
+        let half = delay / 2;
+
+        Duration::from_millis(half + fastrand::u64(0..=delay - half))
+    }
+
     pub async fn try_send_request<T: Serialize>(
         &self,
         method: Method,
         url: &str,
         payload: Option<T>,
     ) -> Result<reqwest::Response, reqwest::Error> {
-        let client = reqwest::Client::new();
-
-        let mut client = client
+        let mut client = self
+            .client
             .request(method, url)
             .header(ACCEPT, "application/json, text/plain, */*")
             .header(ACCEPT_LANGUAGE, "en-US,en;q=0.9")
             .header(
                 AUTHORIZATION,
-                format!("Bearer {}", { &self.access_token.lock().await }),
+                format!("Bearer {}", self.session.token.read().await.value),
             )
-            .header("application-id", APPLICATION_ID);
+            .header("application-id", &self.config.endpoints.application_id);
 
         if let Some(data) = payload {
             client = client.json(&data);
@@ -191,11 +525,132 @@ impl Liveu {
         client.send().await
     }
 
-    pub async fn get_inventories(&self) -> Result<Inventories, Error> {
+    /// Gets the location of the boss_id in the inventories.
+    /// Asks which unit to use when there are multiple and stdin is a terminal.
+    pub fn get_boss_id_location(inventories: &Inventories) -> Result<usize, Error> {
+        let size = inventories.units.len();
+
+        if size == 0 {
+            return Err(Error::NoUnitsFound);
+        }
+
+        if size > 1 {
+            if !std::io::stdin().is_terminal() {
+                return Err(Error::UnitSelectionRequired(size));
+            }
+
+            println!("Found {} units!\n", size);
+
+            for (pos, unit) in inventories.units.iter().enumerate() {
+                println!("({}) {}", pos + 1, unit.display_name());
+            }
+
+            let inp = input()
+                .msg("\nPlease enter which one you want to use (1): ")
+                .inside_err(
+                    1..=size,
+                    format!("Please enter a number between 1 and {}: ", size),
+                )
+                .err("That does not look like a number. Please try again:")
+                .default(1)
+                .get();
+
+            return Ok(inp - 1);
+        }
+
+        Ok(0)
+    }
+}
+
+/// Everything the bot needs from LiveU, so the backend can be swapped out
+#[async_trait]
+pub trait LiveuApi: Clone + Send + Sync + 'static {
+    async fn get_inventories(&self) -> Result<Inventories, Error>;
+
+    async fn get_interfaces(&self, boss_id: &str) -> Result<Vec<Interface>, Error>;
+
+    async fn get_battery(&self, boss_id: &str) -> Result<Battery, Error>;
+
+    async fn get_video(&self, boss_id: &str) -> Result<Video, Error>;
+
+    /// The status of the unit as reported by LiveU Central, e.g. `offline`
+    async fn get_unit_status(&self, boss_id: &str) -> Result<String, Error>;
+
+    async fn start_stream(&self, boss_id: &str) -> Result<(), Error>;
+
+    async fn stop_stream(&self, boss_id: &str) -> Result<(), Error>;
+
+    async fn reboot_unit(&self, boss_id: &str) -> Result<(), Error>;
+
+    async fn get_delay(&self, boss_id: &str) -> Result<Delay, Error>;
+
+    async fn set_delay(&self, boss_id: &str, delay: u64) -> Result<(), Error>;
+
+    /// Turns the interface on the port, e.g. `0` or `wlan0`, on or off
+    async fn set_interface_enabled(
+        &self,
+        boss_id: &str,
+        port: &str,
+        enabled: bool,
+    ) -> Result<(), Error>;
+
+    async fn enable_interface(&self, boss_id: &str, port: &str) -> Result<(), Error> {
+        self.set_interface_enabled(boss_id, port, true).await
+    }
+
+    async fn disable_interface(&self, boss_id: &str, port: &str) -> Result<(), Error> {
+        self.set_interface_enabled(boss_id, port, false).await
+    }
+
+    /// Status of the login token, if the backend uses one
+    async fn token_status(&self) -> Option<TokenStatus> {
+        None
+    }
+
+    /// Gets the state of the unit from its status, video and interfaces
+    async fn get_state(&self, boss_id: &str) -> UnitState {
+        let (status, video, interfaces) = tokio::join!(
+            self.get_unit_status(boss_id),
+            self.get_video(boss_id),
+            self.get_interfaces(boss_id)
+        );
+
+        UnitState::from_parts(status.ok().as_deref(), &video, interfaces.ok().as_deref())
+    }
+
+    async fn get_unit_custom_names(
+        &self,
+        boss_id: &str,
+        custom_names: Option<config::CustomUnitNames>,
+    ) -> Result<Vec<Interface>, Error> {
+        Ok(connected_with_custom_names(
+            self.get_interfaces(boss_id).await?,
+            custom_names,
+        ))
+    }
+}
+
+/// Keeps the connected interfaces and renames their ports to the custom names
+pub fn connected_with_custom_names(
+    interfaces: Vec<Interface>,
+    custom_names: Option<config::CustomUnitNames>,
+) -> Vec<Interface> {
+    let custom_names = custom_names.unwrap_or_default();
+
+    interfaces
+        .into_iter()
+        .filter(|x| x.connected)
+        .map(|x| change_interface_name_to_custom(x, &custom_names))
+        .collect()
+}
+
+#[async_trait]
+impl LiveuApi for Liveu {
+    async fn get_inventories(&self) -> Result<Inventories, Error> {
         let res = self
             .send_request(
                 Method::GET,
-                &format!("{}/inventories", LIVEU_API),
+                &format!("{}/inventories", self.config.endpoints.api),
                 None::<()>,
             )
             .await?;
@@ -205,16 +660,37 @@ impl Liveu {
         }
 
         let res_json: Value = res.json().await?;
-        Ok(serde_json::from_value::<Inventories>(
-            res_json["data"]["inventories"][0].to_owned(),
-        )?)
+        let inventories =
+            serde_json::from_value::<Vec<Inventory>>(res_json["data"]["inventories"].to_owned())?;
+
+        if inventories.is_empty() {
+            return Err(Error::NoInventoriesFound);
+        }
+
+        let units = inventories
+            .into_iter()
+            .flat_map(|inventory| {
+                let Inventory { id, name, units } = inventory;
+
+                units.into_iter().map(move |mut unit| {
+                    unit.inventory_id = id.to_owned();
+                    unit.inventory_name = name.to_owned();
+                    unit
+                })
+            })
+            .collect();
+
+        Ok(Inventories { units })
     }
 
-    pub async fn get_interfaces(&self, boss_id: &str) -> Result<Vec<Interface>, Error> {
+    async fn get_interfaces(&self, boss_id: &str) -> Result<Vec<Interface>, Error> {
         let res = self
             .send_request(
                 Method::GET,
-                &format!("{}/units/{}/status/interfaces", LIVEU_API, &boss_id),
+                &format!(
+                    "{}/units/{}/status/interfaces",
+                    self.config.endpoints.api, &boss_id
+                ),
                 None::<()>,
             )
             .await?;
@@ -222,119 +698,102 @@ impl Liveu {
         match res.status() {
             StatusCode::OK => Ok(res.json().await?),
             StatusCode::NO_CONTENT => Ok(vec![]),
-            _ => Err(Error::NoUnitsFound),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn get_battery(&self, boss_id: &str) -> Result<Battery, Error> {
+    async fn get_battery(&self, boss_id: &str) -> Result<Battery, Error> {
         let res = self
             .send_request(
                 Method::GET,
-                &format!("{}/units/{}/status/battery", LIVEU_API, &boss_id),
+                &format!(
+                    "{}/units/{}/status/battery",
+                    self.config.endpoints.api, &boss_id
+                ),
                 None::<()>,
             )
             .await?;
 
         match res.status() {
             StatusCode::OK => Ok(res.json().await?),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn get_video(&self, boss_id: &str) -> Result<Video, Error> {
+    async fn get_video(&self, boss_id: &str) -> Result<Video, Error> {
         let res = self
             .send_request(
                 Method::GET,
-                &format!("{}/units/{}/status/video", LIVEU_API, &boss_id),
+                &format!(
+                    "{}/units/{}/status/video",
+                    self.config.endpoints.api, &boss_id
+                ),
                 None::<()>,
             )
             .await?;
 
         match res.status() {
             StatusCode::OK => Ok(res.json().await?),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn is_idle(&self, boss_id: &str) -> bool {
-        let video = match self.get_video(boss_id).await {
-            Ok(v) => v,
-            Err(_) => return false,
-        };
-
-        if video.resolution.is_some() && video.bitrate.is_none() {
-            return true;
-        }
-
-        false
-    }
-
-    pub async fn is_streaming(&self, boss_id: &str) -> bool {
-        let video = match self.get_video(boss_id).await {
-            Ok(v) => v,
-            Err(_) => return false,
-        };
-
-        if video.bitrate.is_some() {
-            return true;
-        }
-
-        false
-    }
-
-    pub async fn start_stream(&self, boss_id: &str) -> Result<(), Error> {
+    async fn start_stream(&self, boss_id: &str) -> Result<(), Error> {
         let mut map = HashMap::new();
         map.insert("unit_id", boss_id);
 
         let res = self
             .send_request(
                 Method::POST,
-                &format!("{}/units/{}/stream", LIVEU_API, &boss_id),
+                &format!("{}/units/{}/stream", self.config.endpoints.api, &boss_id),
                 Some(map),
             )
             .await?;
 
         match res.status() {
             StatusCode::CREATED => Ok(()),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn stop_stream(&self, boss_id: &str) -> Result<(), Error> {
+    async fn stop_stream(&self, boss_id: &str) -> Result<(), Error> {
         let res = self
             .send_request(
                 Method::DELETE,
-                &format!("{}/units/{}/stream", LIVEU_API, &boss_id),
+                &format!("{}/units/{}/stream", self.config.endpoints.api, &boss_id),
                 None::<()>,
             )
             .await?;
 
         match res.status() {
             StatusCode::NO_CONTENT => Ok(()),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn reboot_unit(&self, boss_id: &str) -> Result<(), Error> {
+    async fn reboot_unit(&self, boss_id: &str) -> Result<(), Error> {
         let res = self
             .send_request(
                 Method::POST,
-                &format!("{}/units/{}/reboot", LIVEU_API_V2, &boss_id),
+                &format!("{}/units/{}/reboot", self.config.endpoints.api_v2, &boss_id),
                 None::<()>,
             )
             .await?;
 
         match res.status() {
             StatusCode::NO_CONTENT => Ok(()),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn get_delay(&self, boss_id: &str) -> Result<Delay, Error> {
+    async fn get_delay(&self, boss_id: &str) -> Result<Delay, Error> {
         let res = self
             .send_request(
                 Method::GET,
-                &format!("{}/units/{}?fields=delay", LIVEU_API, &boss_id),
+                &format!(
+                    "{}/units/{}?fields=delay",
+                    self.config.endpoints.api, &boss_id
+                ),
                 None::<()>,
             )
             .await?;
@@ -347,15 +806,39 @@ impl Liveu {
                     .ok_or(Error::StatusNotAvailable)?;
                 Ok(Delay { delay })
             }
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    pub async fn set_delay(&self, boss_id: &str, delay: u64) -> Result<(), Error> {
+    async fn get_unit_status(&self, boss_id: &str) -> Result<String, Error> {
+        let res = self
+            .send_request(
+                Method::GET,
+                &format!(
+                    "{}/units/{}?fields=status",
+                    self.config.endpoints.api, &boss_id
+                ),
+                None::<()>,
+            )
+            .await?;
+
+        match res.status() {
+            StatusCode::OK => {
+                let value: serde_json::Value = res.json().await?;
+                let status = value["data"]["unit"]["status"]
+                    .as_str()
+                    .ok_or(Error::StatusNotAvailable)?;
+                Ok(status.to_owned())
+            }
+            status => Err(Self::status_error(status)),
+        }
+    }
+
+    async fn set_delay(&self, boss_id: &str, delay: u64) -> Result<(), Error> {
         let res = self
             .send_request(
                 Method::PUT,
-                &format!("{}/units/{}/delay", LIVEU_API, &boss_id),
+                &format!("{}/units/{}/delay", self.config.endpoints.api, &boss_id),
                 Some(DelayReq {
                     unit: Delay { delay },
                 }),
@@ -364,83 +847,366 @@ impl Liveu {
 
         match res.status() {
             StatusCode::NO_CONTENT => Ok(()),
-            _ => Err(Error::StatusNotAvailable),
+            status => Err(Self::status_error(status)),
         }
     }
 
-    /// Gets the location of the boss_id in the inventories
-    pub fn get_boss_id_location(inventories: &Inventories) -> usize {
-        let size = inventories.units.len();
+    async fn set_interface_enabled(
+        &self,
+        boss_id: &str,
+        port: &str,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let res = self
+            .send_request(
+                Method::PUT,
+                &format!(
+                    "{}/units/{}/interfaces/{}",
+                    self.config.endpoints.api, &boss_id, &port
+                ),
+                Some(InterfaceReq {
+                    interface: InterfaceEnabled { enabled },
+                }),
+            )
+            .await?;
 
-        if size == 0 {
-            panic!("No units found!");
+        match res.status() {
+            StatusCode::OK | StatusCode::NO_CONTENT => Ok(()),
+            status => Err(Self::status_error(status)),
         }
+    }
 
-        if size > 1 {
-            println!("Found {} units!\n", size);
+    async fn token_status(&self) -> Option<TokenStatus> {
+        Some(Liveu::token_status(self).await)
+    }
+}
 
-            for (pos, unit) in inventories.units.iter().enumerate() {
-                println!("({}) {}", pos + 1, unit.name);
-            }
+/// Renames the port of the interface to its custom name
+pub fn change_interface_name_to_custom(
+    mut interface: Interface,
+    custom_names: &config::CustomUnitNames,
+) -> Interface {
+    match interface.port.as_ref() {
+        "eth0" => {
+            interface.port = custom_names.ethernet.to_string();
+        }
+        "wlan0" => {
+            interface.port = custom_names.wifi.to_string();
+        }
+        "0" => {
+            interface.port = custom_names.sim1.to_string();
+        }
+        "1" => {
+            interface.port = custom_names.sim2.to_string();
+        }
+        "2" => {
+            interface.port = custom_names.usb1.to_string();
+        }
+        "3" => {
+            interface.port = custom_names.usb2.to_string();
+        }
+        _ => {}
+    }
 
-            let inp = input()
-                .msg("\nPlease enter which one you want to use (1): ")
-                .inside_err(
-                    1..=size,
-                    format!("Please enter a number between 1 and {}: ", size),
-                )
-                .err("That does not look like a number. Please try again:")
-                .default(1)
-                .get();
+    interface
+}
 
-            return inp - 1;
-        }
+/// A unit kept in memory, for tests that need a `LiveuApi`
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use std::sync::Mutex;
 
-        0
+    #[derive(Debug, Default)]
+    pub struct FakeUnit {
+        pub status: String,
+        /// Connected interfaces, empty like LiveU answers for an offline unit
+        pub interfaces: Vec<Interface>,
+        pub battery: Option<Battery>,
+        /// `None` when the unit is offline
+        pub video: Option<Video>,
+        pub delay: u64,
+        /// Every request fails like LiveU Central does with this status
+        pub unavailable: Option<StatusCode>,
+        /// Changes made to the unit, e.g. `reboot` or `disable 0`
+        pub calls: Vec<String>,
+        /// Every unit of the account
+        pub inventories: Vec<Unit>,
+        /// How long getting the interfaces takes
+        pub interfaces_latency: Duration,
     }
 
-    pub async fn get_unit_custom_names(
-        &self,
-        boss_id: &str,
-        custom_names: Option<config::CustomUnitNames>,
-    ) -> Result<Vec<Interface>, Error> {
-        let custom_names = custom_names.unwrap_or_default();
+    #[derive(Debug, Clone, Default)]
+    pub struct FakeLiveu {
+        pub unit: Arc<Mutex<FakeUnit>>,
+    }
 
-        Ok(self
-            .get_interfaces(boss_id)
-            .await?
-            .into_iter()
-            .filter(|x| x.connected)
-            .map(|x| Self::change_interface_name_to_custom(x, &custom_names))
-            .collect())
-    }
-
-    fn change_interface_name_to_custom(
-        mut interface: Interface,
-        custom_names: &config::CustomUnitNames,
-    ) -> Interface {
-        match interface.port.as_ref() {
-            "eth0" => {
-                interface.port = custom_names.ethernet.to_string();
-            }
-            "wlan0" => {
-                interface.port = custom_names.wifi.to_string();
-            }
-            "0" => {
-                interface.port = custom_names.sim1.to_string();
-            }
-            "1" => {
-                interface.port = custom_names.sim2.to_string();
+    impl FakeLiveu {
+        pub fn new(unit: FakeUnit) -> Self {
+            FakeLiveu {
+                unit: Arc::new(Mutex::new(unit)),
             }
-            "2" => {
-                interface.port = custom_names.usb1.to_string();
+        }
+
+        /// A unit that is streaming over the interfaces
+        pub fn streaming(interfaces: Vec<Interface>) -> Self {
+            Self::new(FakeUnit {
+                status: "streaming".to_string(),
+                battery: Some(battery(80)),
+                video: Some(Video {
+                    resolution: Some("1080p".to_string()),
+                    bitrate: Some(interfaces.iter().map(|i| i.uplink_kbps).sum()),
+                }),
+                interfaces,
+                ..Default::default()
+            })
+        }
+
+        pub fn offline() -> Self {
+            Self::new(FakeUnit {
+                status: "offline".to_string(),
+                ..Default::default()
+            })
+        }
+
+        pub fn calls(&self) -> Vec<String> {
+            self.unit.lock().unwrap().calls.clone()
+        }
+
+        fn with<T>(&self, f: impl FnOnce(&mut FakeUnit) -> Result<T, Error>) -> Result<T, Error> {
+            let mut unit = self.unit.lock().unwrap();
+
+            match unit.unavailable {
+                Some(status) => Err(Error::ApiUnavailable(status)),
+                None => f(&mut unit),
             }
-            "3" => {
-                interface.port = custom_names.usb2.to_string();
+        }
+
+        fn change(&self, call: String) -> Result<(), Error> {
+            self.with(|unit| {
+                unit.calls.push(call);
+                Ok(())
+            })
+        }
+    }
+
+    pub fn interface(port: &str, uplink_kbps: u32) -> Interface {
+        Interface {
+            connected: true,
+            name: port.to_string(),
+            downlink_kbps: 0,
+            uplink_kbps,
+            enabled: true,
+            port: port.to_string(),
+            technology: String::new(),
+            up_signal_quality: 5,
+            down_signal_quality: 5,
+            active_sim: None,
+            is_currently_roaming: false,
+            kbps: uplink_kbps,
+            signal_quality: 5,
+        }
+    }
+
+    pub fn battery(percentage: u8) -> Battery {
+        Battery {
+            connected: true,
+            percentage,
+            run_time_to_empty: 0,
+            discharging: false,
+            charging: false,
+        }
+    }
+
+    #[async_trait]
+    impl LiveuApi for FakeLiveu {
+        async fn get_inventories(&self) -> Result<Inventories, Error> {
+            self.with(|unit| {
+                Ok(Inventories {
+                    units: unit.inventories.clone(),
+                })
+            })
+        }
+
+        async fn get_interfaces(&self, _boss_id: &str) -> Result<Vec<Interface>, Error> {
+            let latency = self.unit.lock().unwrap().interfaces_latency;
+            tokio::time::sleep(latency).await;
+
+            self.with(|unit| Ok(unit.interfaces.clone()))
+        }
+
+        async fn get_battery(&self, _boss_id: &str) -> Result<Battery, Error> {
+            self.with(|unit| unit.battery.clone().ok_or(Error::StatusNotAvailable))
+        }
+
+        async fn get_video(&self, _boss_id: &str) -> Result<Video, Error> {
+            self.with(|unit| unit.video.clone().ok_or(Error::StatusNotAvailable))
+        }
+
+        async fn get_unit_status(&self, _boss_id: &str) -> Result<String, Error> {
+            self.with(|unit| Ok(unit.status.to_owned()))
+        }
+
+        async fn start_stream(&self, _boss_id: &str) -> Result<(), Error> {
+            self.change("start".to_string())
+        }
+
+        async fn stop_stream(&self, _boss_id: &str) -> Result<(), Error> {
+            self.change("stop".to_string())
+        }
+
+        async fn reboot_unit(&self, _boss_id: &str) -> Result<(), Error> {
+            self.change("reboot".to_string())
+        }
+
+        async fn get_delay(&self, _boss_id: &str) -> Result<Delay, Error> {
+            self.with(|unit| Ok(Delay { delay: unit.delay }))
+        }
+
+        async fn set_delay(&self, _boss_id: &str, delay: u64) -> Result<(), Error> {
+            self.with(|unit| {
+                unit.delay = delay;
+                unit.calls.push(format!("delay {}", delay));
+                Ok(())
+            })
+        }
+
+        async fn set_interface_enabled(
+            &self,
+            _boss_id: &str,
+            port: &str,
+            enabled: bool,
+        ) -> Result<(), Error> {
+            let action = if enabled { "enable" } else { "disable" };
+            self.change(format!("{} {}", action, port))?;
+
+            let mut unit = self.unit.lock().unwrap();
+            for interface in unit.interfaces.iter_mut().filter(|i| i.port == port) {
+                interface.enabled = enabled;
             }
-            _ => {}
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fake::interface, *};
+
+    fn token(lifetime: Duration) -> Token {
+        let obtained_at = Instant::now();
+
+        Token {
+            value: String::new(),
+            obtained_at,
+            expires_at: obtained_at + lifetime,
+        }
+    }
+
+    fn refresh_after(lifetime: u64) -> Duration {
+        let token = token(Duration::from_secs(lifetime));
+        token.refresh_at().duration_since(token.obtained_at)
+    }
+
+    #[test]
+    fn refreshes_token_before_it_expires() {
+        assert_eq!(refresh_after(3600), Duration::from_secs(3240));
+        assert_eq!(refresh_after(300), Duration::from_secs(240));
+    }
+
+    #[test]
+    fn short_lived_tokens_do_not_refresh_in_a_loop() {
+        assert_eq!(refresh_after(60), Duration::from_secs(30));
+        assert_eq!(refresh_after(8), Duration::from_secs(5));
+        assert_eq!(refresh_after(0), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max_delay() {
+        let retry = config::Retry::default();
+
+        for _ in 0..100 {
+            let first = Liveu::backoff(0, &retry);
+            assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(500));
+
+            let third = Liveu::backoff(2, &retry);
+            assert!(third >= Duration::from_millis(1000) && third <= Duration::from_millis(2000));
+
+            let capped = Liveu::backoff(30, &retry);
+            assert!(capped >= Duration::from_millis(4000) && capped <= Duration::from_millis(8000));
         }
+    }
+
+    fn video(resolution: Option<&str>, bitrate: Option<u32>) -> Result<Video, Error> {
+        Ok(Video {
+            resolution: resolution.map(|r| r.to_string()),
+            bitrate,
+        })
+    }
+
+    #[test]
+    fn unit_state_from_video() {
+        let interfaces = [interface("0", 1000)];
+        let state = |video: &Result<Video, Error>| {
+            UnitState::from_parts(Some("online"), video, Some(&interfaces))
+        };
+
+        assert_eq!(
+            state(&video(Some("1080p"), Some(3000))),
+            UnitState::Streaming
+        );
+        assert_eq!(state(&video(Some("1080p"), None)), UnitState::Idle);
+        assert_eq!(state(&video(None, None)), UnitState::OnlineNoCamera);
+    }
+
+    #[test]
+    fn unit_state_offline() {
+        let online = video(Some("1080p"), None);
+
+        assert_eq!(
+            UnitState::from_parts(Some("Offline"), &online, None),
+            UnitState::Offline
+        );
+        assert_eq!(
+            UnitState::from_parts(None, &online, Some(&[])),
+            UnitState::Offline
+        );
+        assert_eq!(
+            UnitState::from_parts(None, &Err(Error::StatusNotAvailable), None),
+            UnitState::Offline
+        );
+    }
+
+    #[test]
+    fn only_missing_answers_mean_offline() {
+        let state = |status| UnitState::from_parts(None, &Err(Liveu::status_error(status)), None);
+
+        assert_eq!(state(StatusCode::NOT_FOUND), UnitState::Offline);
+        assert_eq!(state(StatusCode::NO_CONTENT), UnitState::Offline);
+
+        for status in [
+            StatusCode::BAD_REQUEST,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::FORBIDDEN,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::BAD_GATEWAY,
+        ] {
+            assert!(matches!(state(status), UnitState::Unknown(_)), "{}", status);
+        }
+    }
+
+    #[test]
+    fn unit_state_unknown_when_liveu_fails() {
+        let unavailable = Err(Error::ApiUnavailable(StatusCode::SERVICE_UNAVAILABLE));
 
-        interface
+        assert!(matches!(
+            UnitState::from_parts(None, &unavailable, None),
+            UnitState::Unknown(_)
+        ));
+        assert!(matches!(
+            UnitState::from_parts(None, &Err(Error::Timeout), None),
+            UnitState::Unknown(_)
+        ));
     }
 }
@@ -1,95 +1,173 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 use twitch_irc::{
     login,
     transport::tcp::{TCPTransport, TLS},
     TwitchIRCClient,
 };
 
-use crate::{config, liveu};
+use crate::{
+    config, format_duration,
+    liveu::{self, UnitState},
+    poller::{SnapshotReceiver, UnitSnapshot},
+    supervisor,
+    units::Units,
+};
 
 #[derive(Debug, Clone)]
 pub struct Monitor {
     pub client: TwitchIRCClient<TCPTransport<TLS>, login::StaticLoginCredentials>,
     pub config: config::Config,
-    pub liveu: liveu::Liveu,
-    pub boss_id: String,
+    pub units: Units,
+    /// Alias of the unit this monitor watches
+    pub unit: String,
+    pub snapshots: SnapshotReceiver,
 }
 
 impl Monitor {
-    pub fn run(&self) {
-        let modems = self.clone();
-        tokio::spawn(async move { modems.monitor_modems().await });
+    /// Sends a message to chat prefixed with the unit when using multiple units
+    async fn say(&self, message: String) {
+        let tag = match self.units.find(&self.unit) {
+            Some(unit) => self.units.tag(&unit),
+            None => String::new(),
+        };
 
-        let battery = self.clone();
-        tokio::spawn(async move { battery.monitor_battery().await });
+        let _ = self
+            .client
+            .say(self.config.twitch.channel.to_owned(), tag + &message)
+            .await;
     }
 
-    pub async fn monitor_modems(&self) {
-        let mut current_modems = Vec::new();
-        let mut ignore = false;
+    /// Waits for the next snapshot from the poller, `None` when the poller stopped
+    async fn next_snapshot(snapshots: &mut SnapshotReceiver) -> Option<UnitSnapshot> {
+        loop {
+            snapshots.changed().await.ok()?;
 
-        for interface in self
-            .liveu
-            .get_unit_custom_names(&self.boss_id, self.config.custom_port_names.clone())
-            .await
-            .unwrap()
-        {
-            current_modems.push(interface.port);
+            if let Some(snapshot) = snapshots.borrow_and_update().clone() {
+                return Some(snapshot);
+            }
         }
+    }
 
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                self.config.liveu.monitor.modems_interval,
-            ))
-            .await;
+    /// Starts the enabled monitors, they are restarted if they ever stop
+    pub fn run(&self) {
+        let config = &self.config.liveu.monitor;
+
+        if config.modems {
+            println!("Liveu: monitoring modems of {}", self.unit);
+            let modems = self.clone();
+            supervisor::supervise(format!("modem monitor of {}", self.unit), move || {
+                let modems = modems.clone();
+                async move { modems.monitor_modems().await }
+            });
+        }
 
-            if !self.liveu.is_streaming(&self.boss_id).await {
-                ignore = true;
-                continue;
+        if config.battery {
+            println!("Liveu: monitoring battery of {}", self.unit);
+            let battery = self.clone();
+            supervisor::supervise(format!("battery monitor of {}", self.unit), move || {
+                let battery = battery.clone();
+                async move { battery.monitor_battery().await }
+            });
+        }
+
+        if let Some(lifecycle) = &config.lifecycle {
+            println!("Liveu: monitoring state of {}", self.unit);
+            let monitor = self.clone();
+            let lifecycle = lifecycle.to_owned();
+            supervisor::supervise(format!("state monitor of {}", self.unit), move || {
+                let monitor = monitor.clone();
+                let lifecycle = lifecycle.clone();
+                async move { monitor.monitor_lifecycle(&lifecycle).await }
+            });
+        }
+
+        if let Some(bitrate) = &config.bitrate {
+            println!("Liveu: monitoring bitrate of {}", self.unit);
+            let monitor = self.clone();
+            let bitrate = bitrate.to_owned();
+            supervisor::supervise(format!("bitrate monitor of {}", self.unit), move || {
+                let monitor = monitor.clone();
+                let bitrate = bitrate.clone();
+                async move { monitor.monitor_bitrate(&bitrate).await }
+            });
+        }
+
+        if let Some(failures) = config.unreachable_alert {
+            let api = self.clone();
+            supervisor::supervise(format!("api monitor of {}", self.unit), move || {
+                let api = api.clone();
+                async move { api.monitor_api(failures).await }
+            });
+        }
+    }
+
+    /// Tells chat when LiveU Central can't be reached and when it's back
+    pub async fn monitor_api(&self, max_failures: u32) {
+        let mut snapshots = self.snapshots.clone();
+        let mut alerted = false;
+
+        while let Some(snapshot) = Self::next_snapshot(&mut snapshots).await {
+            if !alerted && snapshot.failures >= max_failures.max(1) {
+                alerted = true;
+                self.say("LiveU API unreachable".to_string()).await;
+            }
+
+            if alerted && snapshot.failures == 0 {
+                alerted = false;
+                self.say("LiveU API reachable again".to_string()).await;
             }
+        }
+    }
 
-            let mut current = Vec::new();
-            let mut new_modems = Vec::new();
+    pub async fn monitor_modems(&self) {
+        let monitor = &self.config.liveu.monitor;
+        let mut snapshots = self.snapshots.clone();
+        let mut modems: Option<ModemTracker> = None;
+        let mut boss_id = String::new();
+        let mut ignore = false;
 
-            for interface in self
-                .liveu
-                .get_unit_custom_names(&self.boss_id, self.config.custom_port_names.clone())
-                .await
-                .unwrap()
-            {
-                // we got a new interface
-                if !current_modems.contains(&interface.port) {
-                    // println!("New modem {}", interface.port);
-                    new_modems.push(interface.port.to_owned());
-                    current_modems.push(interface.port.to_owned());
-                }
+        while let Some(snapshot) = Self::next_snapshot(&mut snapshots).await {
+            let interfaces = match snapshot.interfaces {
+                Some(interfaces) => interfaces,
+                None => continue,
+            };
 
-                current.push(interface.port);
+            // the unit got switched, start over
+            if snapshot.boss_id != boss_id {
+                boss_id = snapshot.boss_id.to_owned();
+                modems = None;
             }
 
-            // check diff between current and prev
-            let mut removed_modems = Vec::new();
-            for modem in current_modems.iter() {
-                if !current.contains(modem) {
-                    // println!("Removed modem {}", modem);
-                    removed_modems.push(modem.to_owned());
+            let current: Vec<String> = interfaces.into_iter().map(|i| i.port).collect();
+
+            let modems = match &mut modems {
+                Some(modems) => modems,
+                None => {
+                    modems = Some(ModemTracker::new(current));
+                    continue;
                 }
-            }
+            };
 
-            for rem in removed_modems.iter() {
-                let index = current_modems.iter().position(|m| m == rem).unwrap();
-                current_modems.swap_remove(index);
+            if snapshot.state != UnitState::Streaming {
+                ignore = true;
+                continue;
             }
 
-            let message = Self::generate_modems_message(new_modems, removed_modems);
+            let changes = modems.update(
+                current,
+                snapshot.taken_at,
+                Duration::from_secs(monitor.modem_grace_period),
+                monitor.modem_flap_drops,
+                Duration::from_secs(monitor.modem_flap_window),
+            );
+
+            let message = Self::generate_modems_message(changes, monitor.modem_flap_window);
 
             if !ignore && !message.is_empty() {
-                let _ = self
-                    .client
-                    .say(
-                        self.config.twitch.channel.to_owned(),
-                        "LiveU: ".to_string() + &message,
-                    )
-                    .await;
+                self.say("LiveU: ".to_string() + &message).await;
             }
 
             if ignore {
@@ -98,89 +176,204 @@ impl Monitor {
         }
     }
 
-    fn generate_modems_message(new_modems: Vec<String>, removed_modems: Vec<String>) -> String {
-        let mut message = String::new();
+    /// Everything that changed in one poll as a single line
+    fn generate_modems_message(changes: ModemChanges, flap_window: u64) -> String {
+        let mut parts = Vec::new();
 
-        if !new_modems.is_empty() {
-            let a = if new_modems.len() > 1 { "are" } else { "is" };
+        if !changes.connected.is_empty() {
+            let a = if changes.connected.len() > 1 {
+                "are"
+            } else {
+                "is"
+            };
 
-            message += &format!("{} {} now connected", new_modems.join(", "), a);
+            parts.push(format!(
+                "{} {} now connected",
+                changes.connected.join(", "),
+                a
+            ));
         }
 
-        if !removed_modems.is_empty() {
-            let a = if removed_modems.len() > 1 {
+        if !changes.disconnected.is_empty() {
+            let a = if changes.disconnected.len() > 1 {
                 "have"
             } else {
                 "has"
             };
 
-            message += &format!("{} {} disconnected", removed_modems.join(", "), a);
+            parts.push(format!(
+                "{} {} disconnected",
+                changes.disconnected.join(", "),
+                a
+            ));
         }
 
-        message
-    }
-
-    pub async fn monitor_battery(&self) {
-        let mut prev = liveu::Battery {
-            connected: false,
-            percentage: 255,
-            run_time_to_empty: 0,
-            discharging: false,
-            charging: false,
+        let window = if flap_window.is_multiple_of(60) {
+            format!("{} min", flap_window / 60)
+        } else {
+            format!("{}s", flap_window)
         };
 
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                self.config.liveu.monitor.battery_interval,
-            ))
-            .await;
+        for (modem, drops) in changes.unstable {
+            parts.push(format!(
+                "{} is unstable ({} drops in {})",
+                modem, drops, window
+            ));
+        }
+
+        for modem in changes.stable {
+            parts.push(format!("{} is stable again", modem));
+        }
+
+        parts.join(", ")
+    }
+
+    /// Tells chat when the unit starts or stops streaming, goes offline or comes back
+    pub async fn monitor_lifecycle(&self, messages: &config::Lifecycle) {
+        let mut snapshots = self.snapshots.clone();
+        let mut boss_id = String::new();
+        // the last known state and since when the unit is in it
+        let mut prev: Option<(UnitState, Instant)> = None;
 
-            if !self.liveu.is_streaming(&self.boss_id).await {
+        while let Some(snapshot) = Self::next_snapshot(&mut snapshots).await {
+            // not knowing the state is not a change, the API monitor reports those
+            if snapshot.failed() {
                 continue;
             }
 
-            let battery = if let Ok(battery) = self.liveu.get_battery(&self.boss_id).await {
-                if prev.percentage == 255 {
-                    prev = battery.clone();
+            // the unit got switched, start over
+            if snapshot.boss_id != boss_id {
+                boss_id = snapshot.boss_id.to_owned();
+                prev = None;
+            }
+
+            let state = snapshot.state;
+            let (prev_state, since) = match &prev {
+                Some((prev_state, _)) if *prev_state == state => continue,
+                Some((prev_state, since)) => (prev_state.to_owned(), *since),
+                None => {
+                    prev = Some((state, snapshot.taken_at));
+                    continue;
                 }
+            };
 
-                battery
-            } else {
+            let duration = format_duration(snapshot.taken_at.duration_since(since));
+            let mut changes = Vec::new();
+
+            match (&prev_state, &state) {
+                (UnitState::Streaming, UnitState::Offline) => {
+                    changes.push(&messages.offline_while_streaming)
+                }
+                (_, UnitState::Offline) => changes.push(&messages.offline),
+                (UnitState::Offline, _) => {
+                    changes.push(&messages.online);
+
+                    if state == UnitState::Streaming {
+                        changes.push(&messages.started);
+                    }
+                }
+                (UnitState::Streaming, _) => changes.push(&messages.stopped),
+                (_, UnitState::Streaming) => changes.push(&messages.started),
+                _ => {}
+            }
+
+            if state == UnitState::OnlineNoCamera {
+                changes.push(&messages.no_camera);
+            }
+
+            for message in changes.into_iter().flatten() {
+                self.say(message.replace("{duration}", &duration)).await;
+            }
+
+            prev = Some((state, snapshot.taken_at));
+        }
+    }
+
+    /// Tells chat when the total or a modem bitrate stays low and when it recovers
+    pub async fn monitor_bitrate(&self, alert: &config::BitrateAlert) {
+        let mut snapshots = self.snapshots.clone();
+        let mut bitrate = BitrateWatch::default();
+
+        while let Some(snapshot) = Self::next_snapshot(&mut snapshots).await {
+            for message in bitrate.update(&snapshot, alert) {
+                self.say(message).await;
+            }
+        }
+    }
+
+    pub async fn monitor_battery(&self) {
+        let monitor = &self.config.liveu.monitor;
+        let mut snapshots = self.snapshots.clone();
+        let mut boss_id = String::new();
+        let mut prev: Option<liveu::Battery> = None;
+        let mut percentages: Option<Thresholds> = None;
+        let mut runtimes: Option<Thresholds> = None;
+
+        while let Some(snapshot) = Self::next_snapshot(&mut snapshots).await {
+            // the unit got switched, start over
+            if snapshot.boss_id != boss_id {
+                boss_id = snapshot.boss_id.to_owned();
+                prev = None;
+                percentages = None;
+                runtimes = None;
+            }
+
+            if snapshot.state != UnitState::Streaming {
                 continue;
+            }
+
+            let battery = match snapshot.battery {
+                Some(battery) => battery,
+                None => continue,
             };
 
-            if self.config.liveu.monitor.battery_charging {
-                self.battery_charging(&battery, &prev).await;
+            let prev_battery = prev.take().unwrap_or_else(|| battery.clone());
+
+            if monitor.battery_charging {
+                self.battery_charging(&battery, &prev_battery).await;
             }
 
-            for percentage in &self.config.liveu.monitor.battery_notification {
-                self.battery_percentage_message(*percentage, &battery, &prev)
-                    .await;
+            let percentage = battery.percentage as u32;
+            let percentages = percentages.get_or_insert_with(|| {
+                let notifications = monitor.battery_notification.iter().map(|p| *p as u32);
+                Thresholds::new(notifications, percentage)
+            });
+
+            let hysteresis = monitor.battery_hysteresis as u32;
+            if percentages
+                .update(percentage, |t| (t + hysteresis).min(100))
+                .is_some()
+            {
+                self.battery_percentage_message(&battery).await;
+            }
+
+            // the estimate is only there while running on battery, start over once it's back
+            if battery.discharging && battery.run_time_to_empty != 0 {
+                let minutes = battery.run_time_to_empty;
+                let runtimes = runtimes.get_or_insert_with(|| {
+                    Thresholds::new(monitor.battery_runtime_notification.to_owned(), minutes)
+                });
+
+                if runtimes.update(minutes, |t| t + 5).is_some() {
+                    self.say(format!("LiveU: {} minutes of battery left", minutes))
+                        .await;
+                }
+            } else {
+                runtimes = None;
             }
 
-            prev = battery;
+            prev = Some(battery);
         }
     }
 
     pub async fn battery_charging(&self, battery: &liveu::Battery, prev: &liveu::Battery) {
         if !battery.charging && battery.discharging && !prev.discharging {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    "LiveU: RIP PowerBank / Cable Disconnected".to_string(),
-                )
+            self.say("LiveU: RIP PowerBank / Cable Disconnected".to_string())
                 .await;
         }
 
         if battery.charging && !battery.discharging && !prev.charging {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    "LiveU: Now charging".to_string(),
-                )
-                .await;
+            self.say("LiveU: Now charging".to_string()).await;
         }
 
         if battery.percentage < 100
@@ -188,13 +381,7 @@ impl Monitor {
             && !battery.discharging
             && (prev.charging || prev.discharging)
         {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    "LiveU: Too hot to charge".to_string(),
-                )
-                .await;
+            self.say("LiveU: Too hot to charge".to_string()).await;
         }
 
         if battery.percentage == 100
@@ -203,33 +390,493 @@ impl Monitor {
             && prev.charging
             && !prev.discharging
         {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    "LiveU: Fully charged".to_string(),
-                )
-                .await;
+            self.say("LiveU: Fully charged".to_string()).await;
         }
     }
 
-    pub async fn battery_percentage_message(
-        &self,
-        percentage: u8,
-        current: &liveu::Battery,
-        prev: &liveu::Battery,
-    ) {
-        if current.percentage == percentage && prev.percentage > percentage {
-            let message = format!(
-                "LiveU: Internal battery is at {}% and is {} charging",
-                percentage,
-                if current.charging { "" } else { "not" }
-            );
+    /// Tells chat the battery dropped to one of the notification percentages
+    pub async fn battery_percentage_message(&self, current: &liveu::Battery) {
+        let message = format!(
+            "LiveU: Internal battery is at {}% and is {}",
+            current.percentage,
+            if current.charging {
+                "charging"
+            } else {
+                "not charging"
+            }
+        );
 
-            let _ = self
-                .client
-                .say(self.config.twitch.channel.to_owned(), message)
-                .await;
+        self.say(message).await;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ModemChanges {
+    connected: Vec<String>,
+    disconnected: Vec<String>,
+    /// Modems that started flapping and how often they dropped
+    unstable: Vec<(String, usize)>,
+    /// Modems that stopped flapping and are still connected
+    stable: Vec<String>,
+}
+
+/// Keeps track of the modems chat knows about
+#[derive(Debug)]
+struct ModemTracker {
+    /// Modems chat was last told are connected
+    known: Vec<String>,
+    /// Modems that are gone but still within the grace period
+    missing_since: HashMap<String, Instant>,
+    /// When each modem dropped within the flap window
+    drops: HashMap<String, VecDeque<Instant>>,
+    /// Flapping modems, they are not reported as connected or disconnected
+    unstable: Vec<String>,
+}
+
+impl ModemTracker {
+    fn new(modems: Vec<String>) -> Self {
+        ModemTracker {
+            known: modems,
+            missing_since: HashMap::new(),
+            drops: HashMap::new(),
+            unstable: Vec::new(),
         }
     }
+
+    fn update(
+        &mut self,
+        current: Vec<String>,
+        now: Instant,
+        grace_period: Duration,
+        flap_drops: Option<usize>,
+        flap_window: Duration,
+    ) -> ModemChanges {
+        let mut changes = ModemChanges::default();
+
+        for modem in &current {
+            // back within the grace period, chat never heard it was gone
+            if self.missing_since.remove(modem).is_some() {
+                continue;
+            }
+
+            if !self.known.contains(modem) {
+                self.known.push(modem.to_owned());
+
+                if !self.unstable.contains(modem) {
+                    changes.connected.push(modem.to_owned());
+                }
+            }
+        }
+
+        for modem in self.known.clone() {
+            if current.contains(&modem) {
+                continue;
+            }
+
+            let since = *self
+                .missing_since
+                .entry(modem.to_owned())
+                .or_insert_with(|| {
+                    self.drops
+                        .entry(modem.to_owned())
+                        .or_default()
+                        .push_back(now);
+                    now
+                });
+
+            if now.duration_since(since) >= grace_period {
+                self.missing_since.remove(&modem);
+                self.known.retain(|m| m != &modem);
+
+                if !self.unstable.contains(&modem) {
+                    changes.disconnected.push(modem);
+                }
+            }
+        }
+
+        for (modem, drops) in &mut self.drops {
+            while drops
+                .front()
+                .is_some_and(|drop| now.duration_since(*drop) > flap_window)
+            {
+                drops.pop_front();
+            }
+
+            let flapping = flap_drops.is_some_and(|max| drops.len() >= max.max(1));
+
+            if flapping && !self.unstable.contains(modem) {
+                self.unstable.push(modem.to_owned());
+                changes.unstable.push((modem.to_owned(), drops.len()));
+            }
+        }
+
+        // no drops for a whole window, report it like any other modem again
+        self.drops.retain(|_, drops| !drops.is_empty());
+
+        for modem in std::mem::take(&mut self.unstable) {
+            if self.drops.contains_key(&modem) {
+                self.unstable.push(modem);
+            } else if self.known.contains(&modem) {
+                changes.stable.push(modem);
+            } else {
+                // dropped for good while it was unstable
+                changes.disconnected.push(modem);
+            }
+        }
+
+        changes
+    }
+}
+
+/// Low bitrate alerts of a unit for the total and every modem
+#[derive(Debug, Default)]
+struct BitrateWatch {
+    boss_id: String,
+    total: LowBitrate,
+    modems: HashMap<String, LowBitrate>,
+}
+
+impl BitrateWatch {
+    /// Gets the messages for chat after the snapshot
+    fn update(&mut self, snapshot: &UnitSnapshot, alert: &config::BitrateAlert) -> Vec<String> {
+        let window = Duration::from_secs(alert.duration);
+        let mut messages = Vec::new();
+
+        // a failed poll says nothing about the bitrate, keep the alerts as they are
+        if snapshot.failed() {
+            return messages;
+        }
+
+        // nothing to alert about when not streaming or the unit got switched
+        if snapshot.state != UnitState::Streaming || snapshot.boss_id != self.boss_id {
+            *self = BitrateWatch {
+                boss_id: snapshot.boss_id.to_owned(),
+                ..Default::default()
+            };
+            return messages;
+        }
+
+        let interfaces = match &snapshot.interfaces {
+            Some(interfaces) => interfaces,
+            None => return messages,
+        };
+
+        if let Some(below) = alert.total_below {
+            let bitrate: u32 = interfaces.iter().map(|i| i.uplink_kbps).sum();
+
+            match self
+                .total
+                .update(bitrate < below, snapshot.taken_at, window)
+            {
+                Some(true) => messages.push(format!("LiveU: Total LRT low at {} Kbps", bitrate)),
+                Some(false) => messages.push(format!("LiveU: Total LRT back to {} Kbps", bitrate)),
+                None => {}
+            }
+        }
+
+        if let Some(below) = alert.modem_below {
+            // disconnected modems are reported by the modem monitor
+            self.modems
+                .retain(|port, _| interfaces.iter().any(|i| &i.port == port));
+
+            let mut low = Vec::new();
+            let mut recovered = Vec::new();
+
+            for interface in interfaces {
+                let modem = self.modems.entry(interface.port.to_owned()).or_default();
+                let status = format!("{} {} Kbps", interface.port, interface.uplink_kbps);
+
+                match modem.update(interface.uplink_kbps < below, snapshot.taken_at, window) {
+                    Some(true) => low.push(status),
+                    Some(false) => recovered.push(status),
+                    None => {}
+                }
+            }
+
+            if !low.is_empty() {
+                messages.push(format!("LiveU: Low bitrate on {}", low.join(", ")));
+            }
+
+            if !recovered.is_empty() {
+                messages.push(format!(
+                    "LiveU: Bitrate recovered on {}",
+                    recovered.join(", ")
+                ));
+            }
+        }
+
+        messages
+    }
+}
+
+/// Tracks how long a bitrate has been low, or healthy again after an alert
+#[derive(Debug, Default)]
+struct LowBitrate {
+    /// When the bitrate started to disagree with `alerted`
+    since: Option<Instant>,
+    alerted: bool,
+}
+
+impl LowBitrate {
+    /// `Some(true)` once it has been low for the whole window, `Some(false)` once it
+    /// has been healthy again for the whole window
+    fn update(&mut self, low: bool, at: Instant, window: Duration) -> Option<bool> {
+        if low == self.alerted {
+            self.since = None;
+            return None;
+        }
+
+        let since = *self.since.get_or_insert(at);
+
+        if at.duration_since(since) >= window {
+            self.since = None;
+            self.alerted = low;
+            return Some(low);
+        }
+
+        None
+    }
+}
+
+/// Notifies once a value drops to a threshold and again only after it went back up
+#[derive(Debug)]
+struct Thresholds {
+    /// Every threshold and whether it can notify
+    armed: Vec<(u32, bool)>,
+}
+
+impl Thresholds {
+    /// Thresholds the value is already at or below don't notify until it goes back up
+    fn new(thresholds: impl IntoIterator<Item = u32>, value: u32) -> Self {
+        Thresholds {
+            armed: thresholds.into_iter().map(|t| (t, value > t)).collect(),
+        }
+    }
+
+    /// Gets the lowest threshold the value dropped to, `rearm_at` gives the value a
+    /// threshold is armed again at
+    fn update(&mut self, value: u32, rearm_at: impl Fn(u32) -> u32) -> Option<u32> {
+        let mut crossed: Option<u32> = None;
+
+        for (threshold, armed) in &mut self.armed {
+            if *armed && value <= *threshold {
+                *armed = false;
+                crossed = Some(crossed.map_or(*threshold, |c| c.min(*threshold)));
+            } else if !*armed && value > *threshold && value >= rearm_at(*threshold) {
+                *armed = true;
+            }
+        }
+
+        crossed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modems(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn thresholds_notify_once_until_rearmed() {
+        let mut thresholds = Thresholds::new([50, 10], 80);
+        let rearm_at = |t: u32| t + 3;
+
+        assert_eq!(thresholds.update(60, rearm_at), None);
+        assert_eq!(thresholds.update(50, rearm_at), Some(50));
+        assert_eq!(thresholds.update(49, rearm_at), None);
+        // not above the hysteresis yet
+        assert_eq!(thresholds.update(52, rearm_at), None);
+        assert_eq!(thresholds.update(50, rearm_at), None);
+        assert_eq!(thresholds.update(53, rearm_at), None);
+        assert_eq!(thresholds.update(50, rearm_at), Some(50));
+    }
+
+    #[test]
+    fn thresholds_report_the_lowest_crossed() {
+        let mut thresholds = Thresholds::new([50, 10], 80);
+
+        assert_eq!(thresholds.update(5, |t| t), Some(10));
+        assert_eq!(thresholds.update(5, |t| t), None);
+    }
+
+    #[test]
+    fn thresholds_start_disarmed_below_the_value() {
+        let mut thresholds = Thresholds::new([50, 10], 30);
+
+        assert_eq!(thresholds.update(30, |t| t), None);
+        assert_eq!(thresholds.update(10, |t| t), Some(10));
+    }
+
+    #[test]
+    fn modem_tracker_waits_for_the_grace_period() {
+        let start = Instant::now();
+        let grace = Duration::from_secs(15);
+        let window = Duration::from_secs(120);
+        let mut tracker = ModemTracker::new(modems(&["SIM1", "SIM2"]));
+
+        let changes = tracker.update(modems(&["SIM1"]), start, grace, None, window);
+        assert!(changes.disconnected.is_empty());
+
+        // back in time, chat never hears about it
+        let at = start + Duration::from_secs(10);
+        let changes = tracker.update(modems(&["SIM1", "SIM2"]), at, grace, None, window);
+        assert!(changes.connected.is_empty() && changes.disconnected.is_empty());
+
+        let at = start + Duration::from_secs(20);
+        tracker.update(modems(&["SIM1"]), at, grace, None, window);
+        let at = start + Duration::from_secs(35);
+        let changes = tracker.update(modems(&["SIM1", "USB1"]), at, grace, None, window);
+        assert_eq!(changes.disconnected, ["SIM2"]);
+        assert_eq!(changes.connected, ["USB1"]);
+    }
+
+    #[test]
+    fn modem_tracker_reports_flapping_modems_once() {
+        let start = Instant::now();
+        let window = Duration::from_secs(120);
+        let mut tracker = ModemTracker::new(modems(&["SIM1"]));
+        let mut unstable = Vec::new();
+        let mut reported = Vec::new();
+
+        for i in 0..3 {
+            let at = start + Duration::from_secs(i * 10);
+            let changes = tracker.update(modems(&[]), at, Duration::ZERO, Some(3), window);
+            unstable.extend(changes.unstable);
+            reported.extend(changes.disconnected);
+
+            let at = at + Duration::from_secs(5);
+            let changes = tracker.update(modems(&["SIM1"]), at, Duration::ZERO, Some(3), window);
+            reported.extend(changes.connected);
+        }
+
+        assert_eq!(unstable, [("SIM1".to_string(), 3)]);
+        // every drop up to the one that makes it unstable is reported, reconnecting after is not
+        assert_eq!(reported, ["SIM1", "SIM1", "SIM1", "SIM1", "SIM1"]);
+
+        let at = start + Duration::from_secs(200);
+        let changes = tracker.update(modems(&["SIM1"]), at, Duration::ZERO, Some(3), window);
+        assert_eq!(changes.stable, ["SIM1"]);
+    }
+
+    #[test]
+    fn modem_tracker_reports_modems_lost_while_unstable() {
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        let mut tracker = ModemTracker::new(modems(&["SIM1"]));
+
+        tracker.update(modems(&[]), start, Duration::ZERO, Some(1), window);
+        let at = start + Duration::from_secs(5);
+        tracker.update(modems(&["SIM1"]), at, Duration::ZERO, Some(1), window);
+        let at = start + Duration::from_secs(10);
+        let changes = tracker.update(modems(&[]), at, Duration::ZERO, Some(1), window);
+        assert!(changes.disconnected.is_empty());
+
+        let at = start + Duration::from_secs(100);
+        let changes = tracker.update(modems(&[]), at, Duration::ZERO, Some(1), window);
+        assert_eq!(changes.disconnected, ["SIM1"]);
+        assert!(changes.stable.is_empty());
+    }
+
+    #[test]
+    fn low_bitrate_alerts_after_the_window() {
+        let start = Instant::now();
+        let window = Duration::from_secs(10);
+        let mut bitrate = LowBitrate::default();
+
+        assert_eq!(bitrate.update(true, start, window), None);
+        assert_eq!(
+            bitrate.update(true, start + Duration::from_secs(5), window),
+            None
+        );
+        assert_eq!(
+            bitrate.update(true, start + Duration::from_secs(10), window),
+            Some(true)
+        );
+        assert_eq!(
+            bitrate.update(true, start + Duration::from_secs(20), window),
+            None
+        );
+    }
+
+    #[test]
+    fn low_bitrate_recovers_after_the_window() {
+        let start = Instant::now();
+        let window = Duration::from_secs(10);
+        let mut bitrate = LowBitrate::default();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        bitrate.update(true, at(0), window);
+        assert_eq!(bitrate.update(true, at(10), window), Some(true));
+
+        // hovering around the threshold doesn't count as recovered
+        assert_eq!(bitrate.update(false, at(15), window), None);
+        assert_eq!(bitrate.update(true, at(20), window), None);
+        assert_eq!(bitrate.update(false, at(25), window), None);
+        assert_eq!(bitrate.update(false, at(30), window), None);
+        assert_eq!(bitrate.update(false, at(35), window), Some(false));
+        assert_eq!(bitrate.update(false, at(45), window), None);
+    }
+
+    fn streaming(kbps: u32, at: Instant) -> UnitSnapshot {
+        UnitSnapshot {
+            taken_at: at,
+            boss_id: "boss".to_string(),
+            state: UnitState::Streaming,
+            interfaces: Some(vec![liveu::fake::interface("SIM1", kbps)]),
+            battery: None,
+            video: None,
+            failures: 0,
+        }
+    }
+
+    #[test]
+    fn bitrate_alert_survives_failed_polls() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let alert = config::BitrateAlert {
+            total_below: Some(2000),
+            modem_below: None,
+            duration: 10,
+        };
+        let mut bitrate = BitrateWatch::default();
+
+        assert!(bitrate.update(&streaming(5000, at(0)), &alert).is_empty());
+        bitrate.update(&streaming(1000, at(5)), &alert);
+        assert_eq!(
+            bitrate.update(&streaming(1000, at(15)), &alert),
+            ["LiveU: Total LRT low at 1000 Kbps"]
+        );
+
+        let failed = UnitSnapshot {
+            state: UnitState::Unknown("LiveU API timed out".to_string()),
+            interfaces: None,
+            ..streaming(0, at(20))
+        };
+        assert!(bitrate.update(&failed, &alert).is_empty());
+
+        assert!(bitrate.update(&streaming(3000, at(25)), &alert).is_empty());
+        assert_eq!(
+            bitrate.update(&streaming(3000, at(35)), &alert),
+            ["LiveU: Total LRT back to 3000 Kbps"]
+        );
+    }
+
+    #[test]
+    fn modems_message() {
+        let changes = ModemChanges {
+            connected: modems(&["SIM1", "SIM2"]),
+            disconnected: modems(&["ETH"]),
+            unstable: vec![("USB1".to_string(), 5)],
+            stable: modems(&["WiFi"]),
+        };
+
+        assert_eq!(
+            Monitor::generate_modems_message(changes, 120),
+            "SIM1, SIM2 are now connected, ETH has disconnected, \
+             USB1 is unstable (5 drops in 2 min), WiFi is stable again"
+        );
+    }
 }
@@ -1,5 +1,13 @@
 use anyhow::{Context, Result};
-use liveu_stats_bot::{config::Config, liveu::Liveu, liveu_monitor::Monitor, twitch::Twitch};
+use liveu_stats_bot::{
+    config::Config,
+    error::Error,
+    liveu::{Liveu, LiveuApi},
+    liveu_monitor::Monitor,
+    poller::{Poller, Snapshots},
+    twitch::Twitch,
+    units::{UnitEntry, Units},
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -7,7 +15,10 @@ async fn main() -> Result<()> {
 
     let config = match Config::load("config.json") {
         Ok(c) => c,
-        Err(_) => Config::ask_for_settings().await?,
+        Err(Error::Write(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            Config::ask_for_settings().await?
+        }
+        Err(e) => return Err(e).context("Error loading config.json"),
     };
 
     println!("Liveu: Authenticating...");
@@ -16,41 +27,71 @@ async fn main() -> Result<()> {
         .context("Failed to authenticate. Are your login details correct?")?;
     println!("Liveu: Authenticated");
 
-    let liveu_boss_id = if let Some(boss_id) = &config.liveu.id {
-        boss_id.to_owned()
-    } else {
-        let inventories = liveu
-            .get_inventories()
-            .await
-            .context("Error getting inventories")?;
-        let loc = Liveu::get_boss_id_location(&inventories);
-        inventories.units[loc].id.to_owned()
+    let inventories = liveu
+        .get_inventories()
+        .await
+        .context("Error getting inventories")?;
+
+    let entries = match &config.liveu.units {
+        Some(units) if !units.is_empty() => units
+            .iter()
+            .map(|unit| {
+                Ok(UnitEntry {
+                    alias: unit.alias.to_owned(),
+                    id: inventories.resolve_unit(&unit.id)?.id.to_owned(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+        _ => {
+            let boss_id = if let Some(query) = &config.liveu.id {
+                inventories.resolve_unit(query)?.id.to_owned()
+            } else {
+                let loc = Liveu::get_boss_id_location(&inventories)?;
+                inventories.units[loc].id.to_owned()
+            };
+
+            vec![UnitEntry {
+                alias: "liveu".to_string(),
+                id: boss_id,
+            }]
+        }
     };
+    let units = Units::new(entries);
+
+    let snapshots: Snapshots = units
+        .all()
+        .into_iter()
+        .map(|unit| {
+            let poller = Poller {
+                liveu: liveu.clone(),
+                config: config.clone(),
+                units: units.clone(),
+                unit: unit.alias.to_owned(),
+            };
+
+            (unit.alias, poller.spawn())
+        })
+        .collect();
 
     println!("\nTwitch: Connecting...");
-    let (twitch_client, twitch_join_handle) =
-        Twitch::run(config.clone(), liveu.clone(), liveu_boss_id.to_owned());
+    let (twitch_client, twitch_join_handle) = Twitch::run(
+        config.clone(),
+        liveu.clone(),
+        units.clone(),
+        snapshots.clone(),
+    );
     println!("Twitch: Connected");
 
-    {
+    for unit in units.all() {
         let monitor = Monitor {
             client: twitch_client.clone(),
             config: config.clone(),
-            liveu: liveu.clone(),
-            boss_id: liveu_boss_id.to_owned(),
+            units: units.clone(),
+            unit: unit.alias.to_owned(),
+            snapshots: snapshots[&unit.alias].clone(),
         };
 
-        if config.liveu.monitor.modems {
-            println!("Liveu: monitoring modems");
-            let modems = monitor.clone();
-            tokio::spawn(async move { modems.monitor_modems().await });
-        }
-
-        if config.liveu.monitor.battery {
-            println!("Liveu: monitoring battery");
-            let battery = monitor;
-            tokio::spawn(async move { battery.monitor_battery().await });
-        }
+        monitor.run();
     }
 
     twitch_join_handle.await?;
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+use crate::{
+    config,
+    liveu::{self, LiveuApi, UnitState},
+    supervisor,
+    units::Units,
+};
+
+/// The status of a unit at one point in time
+#[derive(Debug, Clone)]
+pub struct UnitSnapshot {
+    pub taken_at: Instant,
+    pub boss_id: String,
+    pub state: UnitState,
+    /// Connected interfaces with the custom port names, `None` if they could not be fetched
+    pub interfaces: Option<Vec<liveu::Interface>>,
+    pub battery: Option<liveu::Battery>,
+    pub video: Option<liveu::Video>,
+    /// Polls in a row, including this one, where LiveU Central could not be reached
+    pub failures: u32,
+}
+
+impl UnitSnapshot {
+    /// Fetches everything about the unit at once
+    pub async fn fetch<L: LiveuApi>(
+        liveu: &L,
+        boss_id: &str,
+        custom_names: Option<config::CustomUnitNames>,
+    ) -> Self {
+        let (status, video, interfaces, battery) = tokio::join!(
+            liveu.get_unit_status(boss_id),
+            liveu.get_video(boss_id),
+            liveu.get_interfaces(boss_id),
+            liveu.get_battery(boss_id)
+        );
+
+        let state = UnitState::from_parts(
+            status.ok().as_deref(),
+            &video,
+            interfaces.as_ref().ok().map(|i| i.as_slice()),
+        );
+
+        UnitSnapshot {
+            taken_at: Instant::now(),
+            boss_id: boss_id.to_owned(),
+            state,
+            interfaces: interfaces
+                .ok()
+                .map(|i| liveu::connected_with_custom_names(i, custom_names)),
+            battery: battery.ok(),
+            video: video.ok(),
+            failures: 0,
+        }
+    }
+
+    /// LiveU Central could not tell what the unit is doing
+    pub fn failed(&self) -> bool {
+        matches!(self.state, UnitState::Unknown(_))
+    }
+
+    pub fn age(&self) -> Duration {
+        self.taken_at.elapsed()
+    }
+}
+
+pub type SnapshotReceiver = watch::Receiver<Option<UnitSnapshot>>;
+
+/// Latest snapshot of every unit by alias
+pub type Snapshots = HashMap<String, SnapshotReceiver>;
+
+/// Polls a single unit and publishes the result to everyone subscribed
+pub struct Poller<L: LiveuApi> {
+    pub liveu: L,
+    pub config: config::Config,
+    pub units: Units,
+    /// Alias of the unit to poll
+    pub unit: String,
+}
+
+impl<L: LiveuApi> Poller<L> {
+    /// Starts polling in the background, the poller is restarted if it ever stops
+    pub fn spawn(self) -> SnapshotReceiver {
+        let (tx, rx) = watch::channel(None);
+        let tx = Arc::new(tx);
+        let poller = Arc::new(self);
+
+        supervisor::supervise(format!("poller of {}", poller.unit), move || {
+            let poller = poller.clone();
+            let tx = tx.clone();
+
+            async move { poller.poll(&tx).await }
+        });
+
+        rx
+    }
+
+    async fn poll(&self, tx: &watch::Sender<Option<UnitSnapshot>>) {
+        let monitor = &self.config.liveu.monitor;
+        let interval = monitor.poll_interval();
+        let max_backoff = Duration::from_secs(monitor.max_poll_backoff).max(interval);
+
+        // continue counting where a crashed poller left off
+        let mut failures = tx.borrow().as_ref().map(|s| s.failures).unwrap_or(0);
+
+        loop {
+            if let Some(unit) = self.units.find(&self.unit) {
+                let mut snapshot = UnitSnapshot::fetch(
+                    &self.liveu,
+                    &unit.id,
+                    self.config.custom_port_names.clone(),
+                )
+                .await;
+
+                failures = if snapshot.failed() { failures + 1 } else { 0 };
+                snapshot.failures = failures;
+
+                tx.send_replace(Some(snapshot));
+            }
+
+            // wait longer while LiveU Central is having problems
+            let backoff = interval * 2u32.pow(failures.min(6));
+            tokio::time::sleep(backoff.min(max_backoff)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::liveu::fake::{interface, FakeLiveu};
+    use reqwest::StatusCode;
+
+    #[tokio::test]
+    async fn snapshot_of_a_streaming_unit() {
+        let liveu = FakeLiveu::streaming(vec![interface("0", 1500), interface("eth0", 2500)]);
+        let snapshot = UnitSnapshot::fetch(&liveu, "boss", None).await;
+
+        assert_eq!(snapshot.state, UnitState::Streaming);
+        assert!(!snapshot.failed());
+        assert_eq!(snapshot.video.and_then(|v| v.bitrate), Some(4000));
+
+        let ports: Vec<String> = snapshot
+            .interfaces
+            .unwrap()
+            .into_iter()
+            .map(|i| i.port)
+            .collect();
+        assert_eq!(ports, ["SIM1", "ETH"]);
+    }
+
+    #[tokio::test]
+    async fn snapshot_of_an_offline_unit() {
+        let snapshot = UnitSnapshot::fetch(&FakeLiveu::offline(), "boss", None).await;
+
+        assert_eq!(snapshot.state, UnitState::Offline);
+        assert!(!snapshot.failed());
+        assert!(snapshot.battery.is_none());
+    }
+
+    #[tokio::test]
+    async fn snapshot_fails_while_liveu_is_unavailable() {
+        let liveu = FakeLiveu::streaming(vec![interface("0", 1500)]);
+        liveu.unit.lock().unwrap().unavailable = Some(StatusCode::BAD_GATEWAY);
+
+        let snapshot = UnitSnapshot::fetch(&liveu, "boss", None).await;
+
+        assert!(snapshot.failed());
+        assert!(snapshot.interfaces.is_none());
+    }
+}
@@ -0,0 +1,35 @@
+use std::{future::Future, time::Duration};
+use tokio::{task::JoinHandle, time::Instant};
+
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// Keeps a task running, it's started again whenever it stops or panics
+pub fn supervise<F, Fut>(name: String, mut task: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut delay = MIN_RESTART_DELAY;
+
+        loop {
+            let started = Instant::now();
+
+            match tokio::spawn(task()).await {
+                Ok(()) => println!("Liveu: {} stopped", name),
+                Err(e) if e.is_panic() => println!("Liveu: {} crashed", name),
+                Err(_) => return,
+            }
+
+            // it ran fine for a while, don't punish it for an old crash
+            if started.elapsed() > MAX_RESTART_DELAY {
+                delay = MIN_RESTART_DELAY;
+            }
+
+            println!("Liveu: restarting {} in {}s", name, delay.as_secs());
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+        }
+    })
+}
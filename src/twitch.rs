@@ -1,13 +1,23 @@
 use crate::{
+    commands::{
+        Access, Arg, ArgKind, CommandSpec, Cooldowns, Invocation, JobMode, Permission, Registry,
+    },
     config,
     error::Error,
-    liveu::{self, Liveu},
+    format_duration,
+    jobs::Jobs,
+    liveu::{self, LiveuApi, UnitState},
     nginx,
+    poller::{Snapshots, UnitSnapshot},
+    units::{UnitEntry, Units},
 };
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use futures_util::future::{join_all, BoxFuture};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
+use tokio::time::{timeout_at, Instant};
 use twitch_irc::{
     login::StaticLoginCredentials,
     message,
@@ -17,19 +27,27 @@ use twitch_irc::{
 
 const OFFLINE_MSG: &str = "LiveU Offline :(";
 
-pub struct Twitch {
+/// Commands waiting to be confirmed by user and command name
+type PendingConfirmations = Mutex<HashMap<(String, &'static str), (Invocation, Instant)>>;
+
+pub struct Twitch<L: LiveuApi> {
     client: TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>,
-    liveu: Liveu,
-    liveu_boss_id: String,
+    liveu: L,
+    units: Units,
+    snapshots: Snapshots,
     config: config::Config,
-    timeout: Arc<AtomicBool>,
+    commands: Registry<Self>,
+    cooldowns: Cooldowns,
+    jobs: Jobs,
+    pending: PendingConfirmations,
 }
 
-impl Twitch {
+impl<L: LiveuApi> Twitch<L> {
     pub fn run(
         config: config::Config,
-        liveu: Liveu,
-        liveu_boss_id: String,
+        liveu: L,
+        units: Units,
+        snapshots: Snapshots,
     ) -> (
         TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>,
         tokio::task::JoinHandle<()>,
@@ -38,7 +56,6 @@ impl Twitch {
             bot_username,
             bot_oauth,
             channel,
-            mod_only,
             ..
         } = &config.twitch;
 
@@ -57,129 +74,207 @@ impl Twitch {
 
         client.join(channel);
 
-        let mod_only = mod_only.to_owned();
         let client_clone = client.clone();
         let join_handler = tokio::spawn(async move {
-            let t = Self {
+            let t = Arc::new(Self {
                 client: client_clone,
                 liveu,
-                liveu_boss_id,
+                units,
+                snapshots,
+                commands: Self::commands(&config),
                 config,
-                timeout: Arc::new(AtomicBool::new(false)),
-            };
+                cooldowns: Cooldowns::default(),
+                jobs: Jobs::default(),
+                pending: Mutex::default(),
+            });
 
             while let Some(message) = incoming_messages.recv().await {
-                t.handle_chat(message, &mod_only).await;
+                t.handle_chat(message).await;
             }
         });
 
         (client, join_handler)
     }
 
-    async fn handle_chat(&self, message: message::ServerMessage, mod_only: &bool) {
-        let timeout = self.timeout.clone();
-        if timeout.load(Ordering::Acquire) {
-            return;
-        }
-
+    async fn handle_chat(self: &Arc<Self>, message: message::ServerMessage) {
         match message {
-            message::ServerMessage::Notice(msg) => {
-                if msg.message_text == "Login authentication failed" {
-                    panic!("Twitch authentication failed");
-                }
+            message::ServerMessage::Notice(msg)
+                if msg.message_text == "Login authentication failed" =>
+            {
+                panic!("Twitch authentication failed");
             }
             message::ServerMessage::Privmsg(msg) => {
-                let is_owner = msg.badges.contains(&twitch_irc::message::Badge {
-                    name: "broadcaster".to_string(),
-                    version: "1".to_string(),
-                });
-
-                let is_mod = msg.badges.contains(&twitch_irc::message::Badge {
-                    name: "moderator".to_string(),
-                    version: "1".to_string(),
-                });
-
-                let mut user_has_permission = false;
-
-                if let Some(users) = &self.config.twitch.admin_users {
-                    for user in users {
-                        if user.to_lowercase() == msg.sender.login {
-                            user_has_permission = true;
-                            break;
-                        }
-                    }
+                let role = Permission::of(
+                    &msg.sender.login,
+                    &msg.badges,
+                    self.config.twitch.admin_users.as_deref(),
+                );
+
+                let words: Vec<&str> = msg.message_text.split_ascii_whitespace().collect();
+                let command = match words.first().and_then(|t| self.commands.find(t)) {
+                    Some(command) => command,
+                    None => return,
                 };
 
-                if *mod_only && !(is_owner || is_mod || user_has_permission) {
+                if !command.access.allows(&msg.sender.login, role) {
                     return;
                 }
 
-                let command = msg
-                    .message_text
-                    .split_ascii_whitespace()
-                    .next()
-                    .unwrap_or("")
-                    .to_string();
+                let needs_confirm = self
+                    .config
+                    .commands
+                    .confirm
+                    .iter()
+                    .any(|c| c == command.name);
+                let key = (msg.sender.login.to_owned(), command.name);
+
+                let invocation = if needs_confirm && words[1..] == ["confirm"] {
+                    match self.take_pending(&key) {
+                        Some(invocation) => Ok(invocation),
+                        None => {
+                            let reply = format!("LiveU nothing to confirm for {}", words[0]);
+                            let _ = self.client.say(msg.channel_login.to_owned(), reply).await;
+                            return;
+                        }
+                    }
+                } else {
+                    command.parse(
+                        &words[1..],
+                        &msg.sender.login,
+                        &msg.channel_login,
+                        &self.units,
+                        &self.config.custom_port_names.clone().unwrap_or_default(),
+                    )
+                };
 
-                let command = self.get_command(command);
+                let invocation = match invocation {
+                    Ok(invocation) => invocation,
+                    Err(e) => {
+                        let _ = self
+                            .client
+                            .say(msg.channel_login.to_owned(), self.error_message(e))
+                            .await;
+                        return;
+                    }
+                };
 
-                if command == Command::Unknown {
+                // showing a setting, like `!ludelay` without a value, needs no confirmation
+                let shows_only =
+                    matches!(command.job, JobMode::WithArg(_)) && !command.is_job(&invocation);
+
+                if needs_confirm && !shows_only && words[1..] != ["confirm"] {
+                    // the confirmation applies to this unit even if the active one changes
+                    let unit = self.target_unit(&invocation);
+                    let timeout = self.config.commands.confirm_timeout;
+                    let reply = format!(
+                        "@{} type {} confirm within {}s to {} {}",
+                        msg.sender.login, words[0], timeout, command.name, unit.alias
+                    );
+
+                    let mut invocation = invocation;
+                    invocation.unit = Some(unit);
+
+                    let expires = Instant::now() + Duration::from_secs(timeout);
+                    self.pending
+                        .lock()
+                        .unwrap()
+                        .insert(key, (invocation, expires));
+
+                    let _ = self.client.say(msg.channel_login.to_owned(), reply).await;
                     return;
                 }
 
-                let cooldown = self.config.commands.cooldown;
+                let bypass = self.config.commands.cooldown_bypass && role >= Permission::Admin;
 
-                tokio::spawn(async move {
-                    timeout.store(true, Ordering::Release);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(cooldown as u64)).await;
-                    timeout.store(false, Ordering::Release);
-                });
+                if !bypass {
+                    if let Some(remaining) = self.cooldowns.remaining(command, &msg.sender.login) {
+                        if let Some(reply) = &self.config.commands.cooldown_message {
+                            let reply = reply
+                                .replace("{command}", words[0])
+                                .replace("{seconds}", &(remaining.as_secs() + 1).to_string());
+                            let _ = self.client.say(msg.channel_login.to_owned(), reply).await;
+                        }
 
-                let res = if command == Command::Stats || command == Command::Battery {
-                    self.handle_non_permission_commands(command).await
-                } else {
-                    if !(is_owner || user_has_permission) {
                         return;
                     }
+                }
 
-                    self.handle_permission_commands(command, msg.channel_login.to_owned())
-                        .await
+                self.cooldowns.start(command, &msg.sender.login);
+
+                let unit = self.target_unit(&invocation);
+                let channel = msg.channel_login.to_owned();
+                let job = command.is_job(&invocation);
+                let (name, handler) = (command.name, command.handler);
+                let failed_unit = unit.clone();
+                let t = self.clone();
+
+                let task = async move {
+                    let res = match handler(t.clone(), invocation).await {
+                        // the user is waiting on the job, always tell them how it ended
+                        Err(e) if job => Ok(t.tagged(
+                            &failed_unit,
+                            format!("LiveU {} failed: {}", name, t.error_message(e)),
+                        )),
+                        res => res,
+                    };
+                    t.reply(channel, res).await;
                 };
 
-                if let Ok(res) = res {
-                    let _ = self.client.say(msg.channel_login.to_owned(), res).await;
+                if !job {
+                    tokio::spawn(task);
+                    return;
+                }
+
+                if let Err(e) = self.jobs.start(command.name, unit, &msg.sender.login, task) {
+                    let _ = self
+                        .client
+                        .say(msg.channel_login.to_owned(), self.error_message(e))
+                        .await;
                 }
             }
             _ => {}
         };
     }
 
-    async fn handle_non_permission_commands(&self, command: Command) -> Result<String, Error> {
-        match command {
-            Command::Stats => self.generate_liveu_modems_message().await,
-            Command::Battery => self.generate_liveu_battery_message().await,
-            _ => unreachable!(),
+    /// Sends the result of a command to chat
+    async fn reply(&self, channel: String, res: Result<String, Error>) {
+        match res {
+            Ok(res) => {
+                let _ = self.client.say(channel, res).await;
+            }
+            Err(e) => {
+                let _ = self.client.say(channel, self.error_message(e)).await;
+            }
         }
     }
 
-    async fn handle_permission_commands(
-        &self,
-        command: Command,
-        channel: String,
-    ) -> Result<String, Error> {
-        match command {
-            Command::Start => self.generate_liveu_start_message(channel).await,
-            Command::Stop => self.generate_liveu_stop_message(channel).await,
-            Command::Restart => self.generate_liveu_restart_message(channel).await,
-            Command::Reboot => self.generate_liveu_reboot_message(channel).await,
-            Command::Delay => self.toggle_delay(channel).await,
-            _ => unreachable!(),
+    /// Gets the command the user wants to confirm if it didn't expire yet
+    fn take_pending(&self, key: &(String, &'static str)) -> Option<Invocation> {
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+
+        pending.retain(|_, (_, expires)| *expires > now);
+        pending.remove(key).map(|(invocation, _)| invocation)
+    }
+
+    /// Chat reply for a command that could not be parsed
+    fn error_message(&self, error: Error) -> String {
+        match error {
+            Error::UnitNotFound(alias) => format!(
+                "Unknown unit {}, use one of: {}",
+                alias,
+                self.units.aliases().join(", ")
+            ),
+            e => e.to_string(),
         }
     }
 
-    // TODO: This needs a refactor
-    fn get_command(&self, command: String) -> Command {
+    /// Every command the bot responds to
+    fn commands(config: &config::Config) -> Registry<Self> {
         let config::Commands {
+            cooldown,
+            cooldowns,
+            user_cooldown,
             stats,
             battery,
             start,
@@ -187,92 +282,603 @@ impl Twitch {
             restart,
             reboot,
             delay,
+            status,
+            unit,
+            modem,
+            jobs,
+            cancel,
             ..
-        } = &self.config.commands;
+        } = &config.commands;
+
+        let cooldown = |name: &str| cooldowns.get(name).copied().unwrap_or(*cooldown);
+        let user_cooldown = *user_cooldown;
+        let on_off = ["on", "off"].into_iter().map(String::from).collect();
+        let presets = config.liveu.delay.presets.keys().cloned().collect();
+
+        let mut commands = Registry::default();
+
+        commands.register(CommandSpec {
+            name: "stats",
+            triggers: stats.to_owned(),
+            args: vec![Arg::optional("port", ArgKind::Port), Arg::unit()],
+            access: Access::new(Permission::Everyone),
+            cooldown: cooldown("stats"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::stats,
+        });
+
+        commands.register(CommandSpec {
+            name: "battery",
+            triggers: battery.to_owned(),
+            args: vec![Arg::unit()],
+            access: Access::new(Permission::Everyone),
+            cooldown: cooldown("battery"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::battery,
+        });
+
+        commands.register(CommandSpec {
+            name: "start",
+            triggers: vec![start.to_owned()],
+            args: vec![Arg::unit()],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("start"),
+            user_cooldown,
+            job: JobMode::Always,
+            handler: Self::start,
+        });
+
+        commands.register(CommandSpec {
+            name: "stop",
+            triggers: vec![stop.to_owned()],
+            args: vec![Arg::unit()],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("stop"),
+            user_cooldown,
+            job: JobMode::Always,
+            handler: Self::stop,
+        });
+
+        commands.register(CommandSpec {
+            name: "restart",
+            triggers: vec![restart.to_owned()],
+            args: vec![Arg::unit()],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("restart"),
+            user_cooldown,
+            job: JobMode::Always,
+            handler: Self::restart,
+        });
+
+        commands.register(CommandSpec {
+            name: "reboot",
+            triggers: vec![reboot.to_owned()],
+            args: vec![Arg::unit()],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("reboot"),
+            user_cooldown,
+            job: JobMode::Always,
+            handler: Self::reboot,
+        });
+
+        commands.register(CommandSpec {
+            name: "delay",
+            triggers: vec![delay.to_owned()],
+            args: vec![Arg::optional("ms", ArgKind::NumberOr(presets)), Arg::unit()],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("delay"),
+            user_cooldown,
+            job: JobMode::WithArg("ms"),
+            handler: Self::delay,
+        });
+
+        commands.register(CommandSpec {
+            name: "status",
+            triggers: vec![status.to_owned()],
+            args: vec![],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("status"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::status,
+        });
+
+        commands.register(CommandSpec {
+            name: "unit",
+            triggers: vec![unit.to_owned()],
+            args: vec![
+                Arg::optional("unit", ArgKind::Words),
+                Arg::optional("save", ArgKind::OneOf(vec!["save".to_string()])),
+            ],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("unit"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::unit,
+        });
+
+        commands.register(CommandSpec {
+            name: "modem",
+            triggers: vec![modem.to_owned()],
+            args: vec![
+                Arg::required("port", ArgKind::Port),
+                Arg::required("state", ArgKind::OneOf(on_off)),
+                Arg::unit(),
+            ],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("modem"),
+            user_cooldown,
+            job: JobMode::Always,
+            handler: Self::modem,
+        });
+
+        commands.register(CommandSpec {
+            name: "jobs",
+            triggers: vec![jobs.to_owned()],
+            args: vec![],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("jobs"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::jobs,
+        });
+
+        commands.register(CommandSpec {
+            name: "cancel",
+            triggers: vec![cancel.to_owned()],
+            args: vec![Arg::optional("id", ArgKind::NumberOr(vec![]))],
+            access: Access::new(Permission::Admin),
+            cooldown: cooldown("cancel"),
+            user_cooldown,
+            job: JobMode::Never,
+            handler: Self::cancel,
+        });
+
+        commands.configure(&config.commands.permissions, config.twitch.mod_only);
+
+        commands
+    }
+
+    /// The unit from the command or the active one
+    fn target_unit(&self, invocation: &Invocation) -> UnitEntry {
+        invocation
+            .unit
+            .clone()
+            .unwrap_or_else(|| self.units.active())
+    }
+
+    fn stats(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let port = invocation.arg("port");
+
+            if port.is_none() && invocation.unit.is_none() && t.units.is_multi() {
+                return t.generate_liveu_summary_message().await;
+            }
+
+            let unit = t.target_unit(&invocation);
+            let res = match port {
+                Some(port) => t.generate_liveu_modem_message(&unit, port).await,
+                None => t.generate_liveu_modems_message(&unit).await,
+            };
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn battery(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = t.generate_liveu_battery_message(&unit).await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn start(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = t
+                .generate_liveu_start_message(&unit, invocation.channel)
+                .await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn stop(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = t
+                .generate_liveu_stop_message(&unit, invocation.channel)
+                .await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn restart(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = t
+                .generate_liveu_restart_message(&unit, invocation.channel)
+                .await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn reboot(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = t
+                .generate_liveu_reboot_message(&unit, invocation.channel)
+                .await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn delay(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            let res = match invocation.arg("ms") {
+                Some(value) => {
+                    t.change_delay(&unit, value, invocation.channel.to_owned())
+                        .await
+                }
+                None => t.generate_delay_message(&unit).await,
+            };
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
 
-        if stats.contains(&command) {
-            return Command::Stats;
+    fn status(t: Arc<Self>, _: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move { t.generate_status_message().await })
+    }
+
+    fn unit(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let save = invocation.arg("save").is_some();
+            t.switch_unit(invocation.arg("unit"), save).await
+        })
+    }
+
+    fn modem(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let unit = t.target_unit(&invocation);
+            // both are required so parsing already made sure they are there
+            let port = invocation.arg("port").unwrap_or_default();
+            let state = invocation.arg("state").unwrap_or_default();
+
+            let enabled = state.eq_ignore_ascii_case("on");
+            let res = t.toggle_modem(&unit, port, enabled).await;
+
+            res.map(|msg| t.tagged(&unit, msg))
+        })
+    }
+
+    fn jobs(t: Arc<Self>, _: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let jobs = t.jobs.all();
+
+            if jobs.is_empty() {
+                return Ok("LiveU no jobs running".to_string());
+            }
+
+            let jobs = jobs
+                .iter()
+                .map(|job| {
+                    format!(
+                        "#{} {}{} by {} for {}",
+                        job.id,
+                        t.units.tag(&job.unit),
+                        job.command,
+                        job.user,
+                        format_duration(job.running_for())
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            Ok(format!("LiveU jobs: {}", jobs.join(", ")))
+        })
+    }
+
+    fn cancel(t: Arc<Self>, invocation: Invocation) -> BoxFuture<'static, Result<String, Error>> {
+        Box::pin(async move {
+            let jobs = t.jobs.all();
+
+            let id = match (invocation.arg("id"), jobs.as_slice()) {
+                (Some(id), _) => id.parse().unwrap_or_default(),
+                (None, [job]) => job.id,
+                (None, []) => return Ok("LiveU no jobs running".to_string()),
+                (None, _) => {
+                    return Ok(format!(
+                        "LiveU multiple jobs running, use {} <id>",
+                        t.config.commands.cancel
+                    ))
+                }
+            };
+
+            match t.jobs.cancel(id) {
+                Some(job) => Ok(format!(
+                    "LiveU cancelled {}{} (#{})",
+                    t.units.tag(&job.unit),
+                    job.command,
+                    job.id
+                )),
+                None => Ok(format!("LiveU no job #{}", id)),
+            }
+        })
+    }
+
+    /// Prefixes the message with the unit when using multiple units
+    fn tagged(&self, unit: &UnitEntry, message: String) -> String {
+        if message.is_empty() {
+            return message;
         }
 
-        if battery.contains(&command) {
-            return Command::Battery;
+        self.units.tag(unit) + &message
+    }
+
+    /// Short stats of every unit in one message
+    async fn generate_liveu_summary_message(&self) -> Result<String, Error> {
+        let deadline = self.stats_deadline();
+
+        let units = self.units.all();
+        let statuses = join_all(
+            units
+                .iter()
+                .map(|unit| timeout_at(deadline, self.get_interfaces(unit))),
+        );
+        let rtmp = timeout_at(deadline, self.get_rtmp_bitrate());
+        let (statuses, rtmp) = tokio::join!(statuses, rtmp);
+
+        let mut parts = Vec::new();
+
+        for (unit, interfaces) in units.iter().zip(statuses) {
+            let status = match interfaces {
+                Ok(Ok(interfaces)) if interfaces.is_empty() => "Offline".to_string(),
+                Ok(Ok(interfaces)) => {
+                    let total: u32 = interfaces.iter().map(|i| i.uplink_kbps).sum();
+
+                    if total == 0 {
+                        "Online and Ready".to_string()
+                    } else {
+                        format!("{} Kbps ({} modems)", total, interfaces.len())
+                    }
+                }
+                Ok(Err(Error::Timeout)) => "timed out".to_string(),
+                _ => "n/a".to_string(),
+            };
+
+            parts.push(format!("{}: {}", unit.alias, status));
         }
 
-        if start == &command {
-            return Command::Start;
+        let mut message = format!("LiveU {}", parts.join(" | "));
+
+        match rtmp {
+            Ok(Some(Ok(Some(bitrate)))) => message += &format!(" | RTMP: {} Kbps", bitrate),
+            Err(_) => message += " | RTMP: n/a",
+            _ => {}
         }
 
-        if stop == &command {
-            return Command::Stop;
+        Ok(message)
+    }
+
+    /// When the data for the stats command has to be there
+    fn stats_deadline(&self) -> Instant {
+        Instant::now() + Duration::from_millis(self.config.commands.stats_deadline_ms)
+    }
+
+    /// Bitrate of the RTMP server, `None` when it's not configured
+    async fn get_rtmp_bitrate(&self) -> Option<Result<Option<u32>, Error>> {
+        match &self.config.rtmp {
+            Some(rtmp) => Some(nginx::get_rtmp_bitrate(rtmp).await),
+            None => None,
         }
+    }
 
-        if restart == &command {
-            return Command::Restart;
+    /// The latest polled snapshot of the unit if it's recent enough
+    fn fresh_snapshot(&self, unit: &UnitEntry) -> Option<UnitSnapshot> {
+        let max_age = Duration::from_secs(self.config.liveu.monitor.max_snapshot_age);
+
+        self.snapshots
+            .get(&unit.alias)?
+            .borrow()
+            .clone()
+            .filter(|s| s.boss_id == unit.id && s.age() <= max_age)
+    }
+
+    /// Connected interfaces from the snapshot, fetched live when it's stale
+    async fn get_interfaces(&self, unit: &UnitEntry) -> Result<Vec<liveu::Interface>, Error> {
+        if let Some(interfaces) = self.fresh_snapshot(unit).and_then(|s| s.interfaces) {
+            return Ok(interfaces);
         }
 
-        if reboot == &command {
-            return Command::Reboot;
+        self.liveu
+            .get_unit_custom_names(&unit.id, self.config.custom_port_names.clone())
+            .await
+    }
+
+    /// Video status from the snapshot, fetched live when it's stale
+    async fn get_video(&self, unit: &UnitEntry) -> Result<liveu::Video, Error> {
+        if let Some(video) = self.fresh_snapshot(unit).and_then(|s| s.video) {
+            return Ok(video);
         }
 
-        if delay == &command {
-            return Command::Delay;
+        self.liveu.get_video(&unit.id).await
+    }
+
+    /// Battery from the snapshot, fetched live when it's stale
+    async fn get_battery(&self, unit: &UnitEntry) -> Result<liveu::Battery, Error> {
+        if let Some(battery) = self.fresh_snapshot(unit).and_then(|s| s.battery) {
+            return Ok(battery);
         }
 
-        Command::Unknown
+        self.liveu.get_battery(&unit.id).await
     }
 
-    async fn generate_liveu_modems_message(&self) -> Result<String, Error> {
-        let interfaces: Vec<liveu::Interface> = self
-            .liveu
-            .get_unit_custom_names(&self.liveu_boss_id, self.config.custom_port_names.clone())
-            .await?;
+    /// Fetches the modems, video, battery and RTMP bitrate at the same time.
+    /// Anything that doesn't make it before the deadline is shown as n/a.
+    async fn generate_liveu_modems_message(&self, unit: &UnitEntry) -> Result<String, Error> {
+        let deadline = self.stats_deadline();
 
-        if interfaces.is_empty() {
-            return Ok(OFFLINE_MSG.to_string());
-        }
+        let (interfaces, video, battery, rtmp) = tokio::join!(
+            timeout_at(deadline, self.get_interfaces(unit)),
+            timeout_at(deadline, self.get_video(unit)),
+            timeout_at(deadline, self.get_battery(unit)),
+            timeout_at(deadline, self.get_rtmp_bitrate())
+        );
 
         let mut message = String::new();
-        let mut total_bitrate = 0;
-
-        for interface in interfaces.iter() {
-            message = message
-                + &format!(
-                    "{}: {} Kbps{}{}, ",
-                    interface.port,
-                    interface.uplink_kbps,
-                    if !interface.technology.is_empty() {
-                        format!(" ({})", &interface.technology)
-                    } else {
-                        "".to_string()
-                    },
-                    if interface.is_currently_roaming {
-                        " roaming"
-                    } else {
-                        ""
-                    }
-                );
-            total_bitrate += interface.uplink_kbps;
+
+        match interfaces {
+            Ok(Ok(interfaces)) => {
+                if interfaces.is_empty() {
+                    return Ok(OFFLINE_MSG.to_string());
+                }
+
+                let mut total_bitrate = 0;
+
+                for interface in interfaces.iter() {
+                    message = message
+                        + &format!(
+                            "{}: {} Kbps{}{}{}, ",
+                            interface.port,
+                            interface.uplink_kbps,
+                            if self.config.commands.signal_bars {
+                                format!(" {}", signal_bars(interface.signal_quality))
+                            } else {
+                                "".to_string()
+                            },
+                            if !interface.technology.is_empty() {
+                                format!(" ({})", &interface.technology)
+                            } else {
+                                "".to_string()
+                            },
+                            if interface.is_currently_roaming {
+                                " roaming"
+                            } else {
+                                ""
+                            }
+                        );
+                    total_bitrate += interface.uplink_kbps;
+                }
+
+                if total_bitrate == 0 {
+                    return Ok("LiveU Online and Ready".to_string());
+                }
+
+                message += &format!("Total LRT: {} Kbps", total_bitrate);
+            }
+            _ => message += "Modems: n/a",
+        }
+
+        match video {
+            Ok(Ok(liveu::Video {
+                bitrate: Some(bitrate),
+                ..
+            })) => message += &format!(", Video: {} Kbps", bitrate),
+            Ok(Ok(_)) => {}
+            _ => message += ", Video: n/a",
+        }
+
+        match rtmp {
+            Ok(Some(Ok(Some(bitrate)))) => message += &format!(", RTMP: {} Kbps", bitrate),
+            Err(_) => message += ", RTMP: n/a",
+            _ => {}
         }
 
-        if total_bitrate == 0 {
-            return Ok("LiveU Online and Ready".to_string());
+        match battery {
+            Ok(Ok(battery)) => message += &format!(", Battery: {}%", battery.percentage),
+            Err(_) => message += ", Battery: n/a",
+            _ => {}
         }
 
-        message += &format!("Total LRT: {} Kbps", total_bitrate);
+        Ok(message)
+    }
+
+    /// Details of a single modem, e.g. `!lus sim1`
+    async fn generate_liveu_modem_message(
+        &self,
+        unit: &UnitEntry,
+        name: &str,
+    ) -> Result<String, Error> {
+        let port = match self.raw_port(name) {
+            Some(port) => port,
+            None => return Ok(format!("LiveU unknown port {}", name)),
+        };
+
+        // disconnected modems are not in the snapshot, an offline unit has no interfaces at all
+        let interface = match self.liveu.get_interfaces(&unit.id).await {
+            Ok(interfaces) if interfaces.is_empty() => return Ok(OFFLINE_MSG.to_string()),
+            Ok(interfaces) => interfaces.into_iter().find(|i| i.port == port),
+            Err(Error::StatusNotAvailable) => return Ok(OFFLINE_MSG.to_string()),
+            Err(e) => return Err(e),
+        };
+
+        let interface = match interface {
+            Some(interface) => liveu::change_interface_name_to_custom(
+                interface,
+                &self.config.custom_port_names.clone().unwrap_or_default(),
+            ),
+            None => return Ok(format!("LiveU {} not found", name.to_uppercase())),
+        };
 
-        if let Some(rtmp) = &self.config.rtmp {
-            if let Ok(Some(bitrate)) = nginx::get_rtmp_bitrate(rtmp).await {
-                message += &format!(", RTMP: {} Kbps", bitrate);
+        if !interface.connected {
+            let state = if interface.enabled {
+                "disconnected"
+            } else {
+                "disabled"
             };
+
+            return Ok(format!("LiveU {} {}", interface.port, state));
+        }
+
+        let mut message = format!("LiveU {}", interface.port);
+
+        if !interface.technology.is_empty() {
+            message += &format!(" ({})", interface.technology);
+        }
+
+        message += &format!(
+            ": Signal {} {}/5 (up {}/5, down {}/5), Up: {} Kbps, Down: {} Kbps",
+            signal_bars(interface.signal_quality),
+            interface.signal_quality,
+            interface.up_signal_quality,
+            interface.down_signal_quality,
+            interface.uplink_kbps,
+            interface.downlink_kbps
+        );
+
+        if let Some(sim) = &interface.active_sim {
+            message += &format!(", SIM: {}", sim);
+        }
+
+        if interface.is_currently_roaming {
+            message += ", roaming";
         }
 
         Ok(message)
     }
 
-    async fn generate_liveu_battery_message(&self) -> Result<String, Error> {
-        let battery = match self.liveu.get_battery(&self.liveu_boss_id).await {
+    /// Gets the LiveU port of a custom or default port name
+    fn raw_port(&self, name: &str) -> Option<&'static str> {
+        self.config
+            .custom_port_names
+            .clone()
+            .unwrap_or_default()
+            .raw_port(name)
+    }
+
+    async fn generate_liveu_battery_message(&self, unit: &UnitEntry) -> Result<String, Error> {
+        let battery = match self.get_battery(unit).await {
             Ok(b) => b,
-            Err(_) => return Ok(OFFLINE_MSG.to_string()),
+            Err(Error::StatusNotAvailable) => return Ok(OFFLINE_MSG.to_string()),
+            Err(e) => return Err(e),
         };
 
         let estimated_battery_time = {
@@ -318,30 +924,30 @@ impl Twitch {
         Ok(message)
     }
 
-    async fn generate_liveu_start_message(&self, channel: String) -> Result<String, Error> {
-        let video = self.liveu.get_video(&self.liveu_boss_id).await;
-
-        let video = match video {
-            Ok(video) => video,
-            Err(_) => return Ok(OFFLINE_MSG.to_string()),
-        };
-
-        if video.resolution.is_none() {
-            return Ok("LiveU no camera plugged in".to_string());
+    async fn generate_liveu_start_message(
+        &self,
+        unit: &UnitEntry,
+        channel: String,
+    ) -> Result<String, Error> {
+        match self.liveu.get_state(&unit.id).await {
+            UnitState::Idle => {}
+            UnitState::Offline => return Ok(OFFLINE_MSG.to_string()),
+            UnitState::OnlineNoCamera => return Ok("LiveU no camera plugged in".to_string()),
+            UnitState::Streaming => return Ok("LiveU already streaming".to_string()),
+            state @ UnitState::Unknown(_) => return Ok(unknown_state_message(&state)),
         }
 
-        if video.bitrate.is_some() {
-            return Ok("LiveU already streaming".to_string());
+        match self.liveu.start_stream(&unit.id).await {
+            Ok(()) => {}
+            Err(e @ (Error::Timeout | Error::ApiUnavailable(_))) => return Ok(e.to_string()),
+            Err(_) => return Ok("LiveU request error".to_string()),
         }
 
-        if self.liveu.start_stream(&self.liveu_boss_id).await.is_err() {
-            return Ok("LiveU request error".to_string());
-        };
-
         let confirm = DataUsedInThread {
             chat: self.client.clone(),
             liveu: self.liveu.clone(),
-            boss_id: self.liveu_boss_id.to_owned(),
+            boss_id: unit.id.to_owned(),
+            tag: self.units.tag(unit),
             channel,
         };
 
@@ -354,19 +960,29 @@ impl Twitch {
         Ok("LiveU starting stream".to_string())
     }
 
-    async fn generate_liveu_stop_message(&self, channel: String) -> Result<String, Error> {
-        if !self.liveu.is_streaming(&self.liveu_boss_id).await {
-            return Ok("LiveU already stopped".to_string());
+    async fn generate_liveu_stop_message(
+        &self,
+        unit: &UnitEntry,
+        channel: String,
+    ) -> Result<String, Error> {
+        match self.liveu.get_state(&unit.id).await {
+            UnitState::Streaming => {}
+            UnitState::Offline => return Ok(OFFLINE_MSG.to_string()),
+            state @ UnitState::Unknown(_) => return Ok(unknown_state_message(&state)),
+            _ => return Ok("LiveU already stopped".to_string()),
         }
 
-        if self.liveu.stop_stream(&self.liveu_boss_id).await.is_err() {
-            return Ok("LiveU request error".to_string());
-        };
+        match self.liveu.stop_stream(&unit.id).await {
+            Ok(()) => {}
+            Err(e @ (Error::Timeout | Error::ApiUnavailable(_))) => return Ok(e.to_string()),
+            Err(_) => return Ok("LiveU request error".to_string()),
+        }
 
         let confirm = DataUsedInThread {
             chat: self.client.clone(),
             liveu: self.liveu.clone(),
-            boss_id: self.liveu_boss_id.to_owned(),
+            boss_id: unit.id.to_owned(),
+            tag: self.units.tag(unit),
             channel,
         };
 
@@ -379,41 +995,60 @@ impl Twitch {
         Ok("LiveU stopping stream".to_string())
     }
 
-    async fn generate_liveu_restart_message(&self, channel: String) -> Result<String, Error> {
-        if !self.liveu.is_streaming(&self.liveu_boss_id).await {
-            return Ok("LiveU not streaming".to_string());
+    async fn generate_liveu_restart_message(
+        &self,
+        unit: &UnitEntry,
+        channel: String,
+    ) -> Result<String, Error> {
+        match self.liveu.get_state(&unit.id).await {
+            UnitState::Streaming => {}
+            state @ UnitState::Unknown(_) => return Ok(unknown_state_message(&state)),
+            _ => return Ok("LiveU not streaming".to_string()),
         }
 
-        let msg = "LiveU stream restarting".to_string();
+        let msg = self.tagged(unit, "LiveU stream restarting".to_string());
         let _ = self.client.say(channel.to_owned(), msg).await;
 
-        self.generate_liveu_stop_message(channel.to_owned()).await?;
-        tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
-        self.generate_liveu_start_message(channel.to_owned())
+        self.generate_liveu_stop_message(unit, channel.to_owned())
+            .await?;
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        self.generate_liveu_start_message(unit, channel.to_owned())
             .await?;
 
         Ok(String::new())
     }
 
-    async fn generate_liveu_reboot_message(&self, channel: String) -> Result<String, Error> {
-        let is_streaming = self.liveu.is_streaming(&self.liveu_boss_id).await;
+    async fn generate_liveu_reboot_message(
+        &self,
+        unit: &UnitEntry,
+        channel: String,
+    ) -> Result<String, Error> {
+        let is_streaming = match self.liveu.get_state(&unit.id).await {
+            UnitState::Offline => return Ok(OFFLINE_MSG.to_string()),
+            state @ UnitState::Unknown(_) => return Ok(unknown_state_message(&state)),
+            state => state == UnitState::Streaming,
+        };
 
-        let msg = "LiveU Rebooting, please wait approximately 2-3 minutes".to_string();
+        let msg = self.tagged(
+            unit,
+            "LiveU Rebooting, please wait approximately 2-3 minutes".to_string(),
+        );
         let _ = self.client.say(channel.to_owned(), msg).await;
 
         if is_streaming {
-            self.generate_liveu_stop_message(channel.to_owned()).await?;
-            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+            self.generate_liveu_stop_message(unit, channel.to_owned())
+                .await?;
+            tokio::time::sleep(Duration::from_secs(4)).await;
         }
 
-        self.liveu.reboot_unit(&self.liveu_boss_id).await?;
-        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+        self.liveu.reboot_unit(&unit.id).await?;
+        tokio::time::sleep(Duration::from_secs(30)).await;
 
         let mut attempts = 0;
         let max_attempts = 20;
 
-        while !self.liveu.is_idle(&self.liveu_boss_id).await && attempts != max_attempts {
-            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        while self.liveu.get_state(&unit.id).await != UnitState::Idle && attempts != max_attempts {
+            tokio::time::sleep(Duration::from_secs(10)).await;
             attempts += 1;
         }
 
@@ -421,10 +1056,10 @@ impl Twitch {
             return Ok("LiveU took too long to reboot".to_string());
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
 
         if is_streaming {
-            self.generate_liveu_start_message(channel.to_owned())
+            self.generate_liveu_start_message(unit, channel.to_owned())
                 .await?;
             return Ok(String::new());
         }
@@ -432,71 +1067,244 @@ impl Twitch {
         Ok("LiveU rebooted successfully".to_string())
     }
 
-    async fn toggle_delay(&self, channel: String) -> Result<String, Error> {
-        let is_streaming = self.liveu.is_streaming(&self.liveu_boss_id).await;
+    /// Lists the units or switches the active unit.
+    /// `!luunit`, `!luunit 2`, `!luunit cam2` or `!luunit backup save`
+    async fn switch_unit(&self, query: Option<&str>, save: bool) -> Result<String, Error> {
+        let active = self.units.active();
+
+        let query = match query {
+            Some(query) => query,
+            None => {
+                let inventories = self.liveu.get_inventories().await?;
+                let list = inventories
+                    .units
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, unit)| {
+                        format!(
+                            "({}) {}: {}{}",
+                            pos + 1,
+                            unit.display_name(),
+                            unit.status,
+                            if unit.id == active.id {
+                                " (active)"
+                            } else {
+                                ""
+                            }
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                return Ok(format!("LiveU units: {}", list.join(", ")));
+            }
+        };
 
-        if is_streaming {
-            self.generate_liveu_stop_message(channel.to_owned()).await?;
-            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        if let Some(entry) = self.units.find(query) {
+            self.units.set_active(&entry.alias);
+
+            // only the ids of the units are in the config, not which one is active
+            let mut message = format!("LiveU now using {}", entry.alias);
+            if save {
+                message += " (not saved, the first unit is used after a restart)";
+            }
+
+            return Ok(message);
         }
 
-        let current_delay = self.liveu.get_delay(&self.liveu_boss_id).await?;
-        let delay = if current_delay.delay == 1000 {
-            (5000, "LiveU high resiliency mode")
-        } else {
-            (1000, "LiveU low delay mode")
+        let inventories = self.liveu.get_inventories().await?;
+        let unit = match query.parse::<usize>() {
+            Ok(index) if index >= 1 => inventories.units.get(index - 1),
+            _ => inventories.find_unit(query),
         };
 
-        self.liveu.set_delay(&self.liveu_boss_id, delay.0).await?;
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        let unit = match unit {
+            Some(unit) => unit,
+            None => return Ok(format!("LiveU unit {} not found", query)),
+        };
 
-        if is_streaming {
-            self.generate_liveu_start_message(channel.to_owned())
+        self.units.set_id(&active.alias, &unit.id);
+
+        let mut message = format!("LiveU now using {}", unit.display_name());
+
+        if save {
+            // with `units` configured the id of `liveu` is ignored, save it in the entry
+            let alias = self
+                .config
+                .liveu
+                .units
+                .as_ref()
+                .is_some_and(|units| !units.is_empty())
+                .then_some(active.alias.as_str());
+
+            match config::Config::save_unit_id(alias, &unit.id) {
+                Ok(()) => message += " (saved)",
+                Err(e) => message += &format!(" (could not save config: {})", e),
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Turns a modem on or off, e.g. `!lumodem sim2 off`
+    async fn toggle_modem(
+        &self,
+        unit: &UnitEntry,
+        name: &str,
+        enabled: bool,
+    ) -> Result<String, Error> {
+        let port = match self.raw_port(name) {
+            Some(port) => port,
+            None => return Ok(format!("LiveU unknown port {}", name)),
+        };
+
+        self.liveu
+            .set_interface_enabled(&unit.id, port, enabled)
+            .await?;
+
+        let state = if enabled { "enabled" } else { "disabled" };
+
+        // confirm the change actually happened
+        for _ in 0..5 {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+
+            let interfaces = match self.liveu.get_interfaces(&unit.id).await {
+                Ok(interfaces) => interfaces,
+                Err(_) => continue,
+            };
+
+            if interfaces
+                .iter()
+                .any(|i| i.port == port && i.enabled == enabled)
+            {
+                return Ok(format!("LiveU {} {}", name.to_uppercase(), state));
+            }
+        }
+
+        Ok(format!(
+            "LiveU {} might not be {}, check LiveU Central",
+            name.to_uppercase(),
+            state
+        ))
+    }
+
+    async fn generate_status_message(&self) -> Result<String, Error> {
+        let token = match self.liveu.token_status().await {
+            Some(token) => token,
+            None => return Ok("LiveU token status not available".to_string()),
+        };
+
+        Ok(format!(
+            "LiveU token age: {}, expires in: {}",
+            format_duration(token.age),
+            format_duration(token.expires_in)
+        ))
+    }
+
+    /// Shows the delay or sets it to a value in ms or a preset, e.g. `!ludelay low`
+    async fn change_delay(
+        &self,
+        unit: &UnitEntry,
+        value: &str,
+        channel: String,
+    ) -> Result<String, Error> {
+        let settings = &self.config.liveu.delay;
+
+        let delay = match settings.parse(value) {
+            Some(delay) => delay,
+            None => return Ok(format!("LiveU unknown delay {}", value)),
+        };
+
+        if !(settings.min..=settings.max).contains(&delay) {
+            return Ok(format!(
+                "LiveU delay must be between {} and {} ms",
+                settings.min, settings.max
+            ));
+        }
+
+        let is_streaming = match self.liveu.get_state(&unit.id).await {
+            UnitState::Offline => return Ok(OFFLINE_MSG.to_string()),
+            state @ UnitState::Unknown(_) => return Ok(unknown_state_message(&state)),
+            state => state == UnitState::Streaming,
+        };
+
+        let restart = is_streaming && settings.restart_stream;
+
+        if restart {
+            self.generate_liveu_stop_message(unit, channel.to_owned())
                 .await?;
+            tokio::time::sleep(Duration::from_secs(4)).await;
+        }
+
+        self.liveu.set_delay(&unit.id, delay).await?;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        if restart {
+            self.generate_liveu_start_message(unit, channel.to_owned())
+                .await?;
+        }
+
+        Ok(format!(
+            "LiveU delay set to {}",
+            delay_name(settings, delay)
+        ))
+    }
+
+    async fn generate_delay_message(&self, unit: &UnitEntry) -> Result<String, Error> {
+        match self.liveu.get_delay(&unit.id).await {
+            Ok(delay) => Ok(format!(
+                "LiveU delay is {}",
+                delay_name(&self.config.liveu.delay, delay.delay)
+            )),
+            Err(Error::StatusNotAvailable) => Ok(OFFLINE_MSG.to_string()),
+            Err(e) => Err(e),
         }
+    }
+}
 
-        Ok(delay.1.to_string())
+fn unknown_state_message(state: &UnitState) -> String {
+    format!("LiveU status {}", state)
+}
+
+/// Formats a delay like 1000 ms (low)
+fn delay_name(settings: &config::Delay, delay: u64) -> String {
+    match settings.preset_name(delay) {
+        Some(name) => format!("{} ms ({})", delay, name),
+        None => format!("{} ms", delay),
     }
 }
 
-#[derive(PartialEq, Eq)]
-enum Command {
-    Stats,
-    Battery,
-    Start,
-    Stop,
-    Restart,
-    Reboot,
-    Delay,
-    Unknown,
+/// Signal quality from 0 to 5 as bars
+fn signal_bars(quality: u32) -> String {
+    let filled = quality.min(5) as usize;
+
+    "▮".repeat(filled) + &"▯".repeat(5 - filled)
 }
 
-struct DataUsedInThread {
+struct DataUsedInThread<L: LiveuApi> {
     chat: TwitchIRCClient<TCPTransport<TLS>, StaticLoginCredentials>,
-    liveu: Liveu,
+    liveu: L,
     boss_id: String,
+    tag: String,
     channel: String,
 }
 
-impl DataUsedInThread {
+impl<L: LiveuApi> DataUsedInThread<L> {
     async fn confirm_action(
         &self,
         max_attempts: u8,
-        should_have_bitrate: bool,
+        should_be_streaming: bool,
         success: String,
         not_success: String,
     ) {
         let mut attempts = 0;
 
         while attempts != max_attempts {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-
-            let video = self.liveu.get_video(&self.boss_id).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
 
-            if let Ok(video) = video {
-                if video.bitrate.is_some() == should_have_bitrate {
-                    break;
-                }
+            match self.liveu.get_state(&self.boss_id).await {
+                UnitState::Unknown(_) => {}
+                state if (state == UnitState::Streaming) == should_be_streaming => break,
+                _ => {}
             }
 
             attempts += 1;
@@ -504,15 +1312,357 @@ impl DataUsedInThread {
 
         if attempts == max_attempts {
             let msg = format!(
-                "LiveU {} stream took too long might not have worked",
-                not_success
+                "{}LiveU {} stream took too long might not have worked",
+                self.tag, not_success
             );
             let _ = self.chat.say(self.channel.to_owned(), msg).await;
 
             return;
         }
 
-        let msg = format!("LiveU streaming {} successfully", success);
+        let msg = format!("{}LiveU streaming {} successfully", self.tag, success);
         let _ = self.chat.say(self.channel.to_owned(), msg).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::liveu::fake::{interface, FakeLiveu};
+
+    fn units() -> Units {
+        Units::new(vec![
+            UnitEntry {
+                alias: "cam1".to_string(),
+                id: "boss1".to_string(),
+            },
+            UnitEntry {
+                alias: "cam2".to_string(),
+                id: "boss2".to_string(),
+            },
+        ])
+    }
+
+    /// The bot with the default commands, chat is never connected to
+    fn twitch(liveu: FakeLiveu, units: Units) -> Arc<Twitch<FakeLiveu>> {
+        let config = config::tests::config();
+        let (_, client) = TwitchIRCClient::new(ClientConfig::default());
+
+        Arc::new(Twitch {
+            client,
+            liveu,
+            units,
+            snapshots: Snapshots::new(),
+            commands: Twitch::<FakeLiveu>::commands(&config),
+            config,
+            cooldowns: Cooldowns::default(),
+            jobs: Jobs::default(),
+            pending: Mutex::default(),
+        })
+    }
+
+    fn parse(t: &Twitch<FakeLiveu>, message: &str) -> Result<Invocation, Error> {
+        let words: Vec<&str> = message.split_ascii_whitespace().collect();
+        let command = t.commands.find(words[0]).unwrap();
+
+        command.parse(
+            &words[1..],
+            "viewer",
+            "channel",
+            &t.units,
+            &config::CustomUnitNames::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn unit_alias_goes_anywhere() {
+        let t = twitch(FakeLiveu::default(), units());
+
+        for message in ["!lus cam2 sim1", "!lus sim1 cam2"] {
+            let invocation = parse(&t, message).unwrap();
+            assert_eq!(
+                invocation.unit.as_ref().map(|u| u.alias.as_str()),
+                Some("cam2")
+            );
+            assert_eq!(
+                invocation.args.get("port").map(|p| p.as_str()),
+                Some("sim1")
+            );
+        }
+
+        let invocation = parse(&t, "!ludelay cam1 2500").unwrap();
+        assert_eq!(invocation.arg("ms"), Some("2500"));
+
+        let invocation = parse(&t, "!lumodem cam1 sim2 off").unwrap();
+        assert_eq!(
+            invocation.unit.as_ref().map(|u| u.alias.as_str()),
+            Some("cam1")
+        );
+        assert_eq!(invocation.arg("port"), Some("sim2"));
+        assert_eq!(invocation.arg("state"), Some("off"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn confirmations_expire() {
+        let t = twitch(FakeLiveu::default(), units());
+        let key = ("mod".to_string(), "restart");
+        let pending = |message| {
+            let mut invocation = parse(&t, message).unwrap();
+            invocation.unit = Some(t.target_unit(&invocation));
+            (invocation, Instant::now() + Duration::from_secs(30))
+        };
+
+        t.pending
+            .lock()
+            .unwrap()
+            .insert(key.clone(), pending("!lus cam2"));
+        t.units.set_active("cam1");
+
+        let invocation = t.take_pending(&key).unwrap();
+        assert_eq!(invocation.unit.map(|u| u.alias), Some("cam2".to_string()));
+        assert!(t.take_pending(&key).is_none());
+
+        t.pending
+            .lock()
+            .unwrap()
+            .insert(key.clone(), pending("!lus"));
+        tokio::time::advance(Duration::from_secs(31)).await;
+
+        assert!(t.take_pending(&key).is_none());
+        assert!(t.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn toggling_a_modem_waits_for_liveu() {
+        let t = twitch(
+            FakeLiveu::streaming(vec![interface("0", 1500), interface("1", 500)]),
+            units(),
+        );
+        let unit = t.units.active();
+
+        assert_eq!(
+            t.toggle_modem(&unit, "sim1", false).await.unwrap(),
+            "LiveU SIM1 disabled"
+        );
+        assert_eq!(
+            t.toggle_modem(&unit, "wifi", true).await.unwrap(),
+            "LiveU WIFI might not be enabled, check LiveU Central"
+        );
+        assert_eq!(
+            t.toggle_modem(&unit, "x", true).await.unwrap(),
+            "LiveU unknown port x"
+        );
+
+        assert_eq!(t.liveu.calls(), ["disable 0", "enable wlan0"]);
+        let interfaces = t.liveu.unit.lock().unwrap().interfaces.clone();
+        assert!(!interfaces[0].enabled);
+        assert!(interfaces[1].enabled);
+    }
+
+    #[tokio::test]
+    async fn stats_message() {
+        let mut sim1 = interface("0", 1500);
+        sim1.technology = "LTE".to_string();
+        let mut eth = interface("eth0", 2500);
+        eth.is_currently_roaming = true;
+        let t = twitch(FakeLiveu::streaming(vec![sim1, eth]), units());
+
+        assert_eq!(
+            t.generate_liveu_modems_message(&t.units.active())
+                .await
+                .unwrap(),
+            "SIM1: 1500 Kbps (LTE), ETH: 2500 Kbps roaming, Total LRT: 4000 Kbps, \
+             Video: 4000 Kbps, Battery: 80%"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_show_what_arrived_before_the_deadline() {
+        let liveu = FakeLiveu::streaming(vec![interface("0", 1500)]);
+        liveu.unit.lock().unwrap().interfaces_latency = Duration::from_secs(60);
+        let t = twitch(liveu, units());
+
+        assert_eq!(
+            t.generate_liveu_modems_message(&t.units.active())
+                .await
+                .unwrap(),
+            "Modems: n/a, Video: 1500 Kbps, Battery: 80%"
+        );
+    }
+
+    #[tokio::test]
+    async fn stats_while_liveu_is_unavailable() {
+        let liveu = FakeLiveu::streaming(vec![interface("0", 1500)]);
+        liveu.unit.lock().unwrap().unavailable = Some(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let t = twitch(liveu, units());
+
+        assert_eq!(
+            t.generate_liveu_modems_message(&t.units.active())
+                .await
+                .unwrap(),
+            "Modems: n/a, Video: n/a"
+        );
+    }
+
+    #[tokio::test]
+    async fn stats_of_every_unit() {
+        let t = twitch(
+            FakeLiveu::streaming(vec![interface("0", 1500), interface("1", 500)]),
+            units(),
+        );
+
+        let summary = Twitch::stats(t.clone(), parse(&t, "!lus").unwrap()).await;
+        assert_eq!(
+            summary.unwrap(),
+            "LiveU cam1: 2000 Kbps (2 modems) | cam2: 2000 Kbps (2 modems)"
+        );
+
+        let single = Twitch::stats(t.clone(), parse(&t, "!lus cam2").unwrap()).await;
+        assert!(single.unwrap().starts_with("[cam2] SIM1: 1500 Kbps"));
+
+        t.liveu.unit.lock().unwrap().interfaces.clear();
+        assert_eq!(
+            t.generate_liveu_summary_message().await.unwrap(),
+            "LiveU cam1: Offline | cam2: Offline"
+        );
+    }
+
+    #[tokio::test]
+    async fn modem_details() {
+        let mut sim1 = interface("0", 1500);
+        sim1.technology = "LTE".to_string();
+        let mut sim2 = interface("1", 0);
+        sim2.connected = false;
+
+        let t = twitch(FakeLiveu::streaming(vec![sim1, sim2]), units());
+        let unit = t.units.active();
+
+        assert_eq!(
+            t.generate_liveu_modem_message(&unit, "sim1").await.unwrap(),
+            "LiveU SIM1 (LTE): Signal ▮▮▮▮▮ 5/5 (up 5/5, down 5/5), Up: 1500 Kbps, Down: 0 Kbps"
+        );
+        assert_eq!(
+            t.generate_liveu_modem_message(&unit, "sim2").await.unwrap(),
+            "LiveU SIM2 disconnected"
+        );
+        assert_eq!(
+            t.generate_liveu_modem_message(&unit, "usb1").await.unwrap(),
+            "LiveU USB1 not found"
+        );
+        assert_eq!(
+            t.generate_liveu_modem_message(&unit, "modem9")
+                .await
+                .unwrap(),
+            "LiveU unknown port modem9"
+        );
+    }
+
+    #[tokio::test]
+    async fn modem_details_of_an_offline_unit() {
+        let t = twitch(FakeLiveu::offline(), units());
+
+        assert_eq!(
+            t.generate_liveu_modem_message(&t.units.active(), "sim1")
+                .await
+                .unwrap(),
+            OFFLINE_MSG
+        );
+    }
+
+    #[tokio::test]
+    async fn only_changing_the_delay_is_a_job() {
+        let t = twitch(FakeLiveu::default(), units());
+        let delay = t.commands.find("!ludelay").unwrap();
+
+        assert!(!delay.is_job(&parse(&t, "!ludelay cam2").unwrap()));
+        assert!(delay.is_job(&parse(&t, "!ludelay 2500").unwrap()));
+        assert!(delay.is_job(&parse(&t, "!ludelay low cam2").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn delay_messages() {
+        let liveu = FakeLiveu::streaming(vec![interface("0", 1500)]);
+        liveu.unit.lock().unwrap().delay = 5000;
+        let t = twitch(liveu.clone(), units());
+        let unit = t.units.active();
+        let change = |value: &'static str| {
+            let t = t.clone();
+            let unit = unit.clone();
+            async move { t.change_delay(&unit, value, String::new()).await.unwrap() }
+        };
+
+        assert_eq!(
+            t.generate_delay_message(&unit).await.unwrap(),
+            "LiveU delay is 5000 ms (high)"
+        );
+        assert_eq!(
+            change("100").await,
+            "LiveU delay must be between 500 and 10000 ms"
+        );
+        assert_eq!(
+            change("20000").await,
+            "LiveU delay must be between 500 and 10000 ms"
+        );
+        assert_eq!(change("medium").await, "LiveU unknown delay medium");
+        assert!(liveu.calls().is_empty());
+
+        let t = twitch(FakeLiveu::offline(), units());
+        assert_eq!(
+            t.change_delay(&unit, "low", String::new()).await.unwrap(),
+            OFFLINE_MSG
+        );
+    }
+
+    fn account_unit(name: &str, id: &str) -> liveu::Unit {
+        liveu::Unit {
+            id: id.to_string(),
+            reg_code: String::new(),
+            status: "idle".to_string(),
+            name: name.to_string(),
+            inventory_id: String::new(),
+            inventory_name: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn switch_to_a_unit_name_with_spaces() {
+        let liveu = FakeLiveu::default();
+        liveu.unit.lock().unwrap().inventories = vec![
+            account_unit("Main LU800", "boss1"),
+            account_unit("Backup LU600", "boss3"),
+        ];
+        let single = Units::new(vec![UnitEntry {
+            alias: "liveu".to_string(),
+            id: "boss1".to_string(),
+        }]);
+        let t = twitch(liveu, single);
+
+        let invocation = parse(&t, "!luunit Backup LU600 save").unwrap();
+        assert_eq!(invocation.arg("unit"), Some("Backup LU600"));
+        assert_eq!(invocation.arg("save"), Some("save"));
+
+        let invocation = parse(&t, "!luunit save").unwrap();
+        assert_eq!(invocation.arg("unit"), Some("save"));
+        assert_eq!(invocation.arg("save"), None);
+
+        assert_eq!(
+            t.switch_unit(Some("backup lu600"), false).await.unwrap(),
+            "LiveU now using Backup LU600"
+        );
+        assert_eq!(t.units.active().id, "boss3");
+    }
+
+    #[tokio::test]
+    async fn unknown_unit_alias() {
+        let t = twitch(FakeLiveu::default(), units());
+
+        assert!(matches!(
+            parse(&t, "!lus cam3"),
+            Err(Error::UnitNotFound(alias)) if alias == "cam3"
+        ));
+        assert!(matches!(
+            parse(&t, "!lumodem cam1 sim2"),
+            Err(Error::Usage(_))
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitEntry {
+    pub alias: String,
+    pub id: String,
+}
+
+#[derive(Debug)]
+struct UnitsInner {
+    entries: Vec<UnitEntry>,
+    active: usize,
+}
+
+/// The units the bot controls, shared between chat and the monitors
+#[derive(Debug, Clone)]
+pub struct Units {
+    inner: Arc<RwLock<UnitsInner>>,
+}
+
+impl Units {
+    pub fn new(entries: Vec<UnitEntry>) -> Self {
+        Units {
+            inner: Arc::new(RwLock::new(UnitsInner { entries, active: 0 })),
+        }
+    }
+
+    pub fn all(&self) -> Vec<UnitEntry> {
+        self.inner.read().unwrap().entries.clone()
+    }
+
+    /// True when more than one unit is configured
+    pub fn is_multi(&self) -> bool {
+        self.inner.read().unwrap().entries.len() > 1
+    }
+
+    /// The unit commands use when no alias is given
+    pub fn active(&self) -> UnitEntry {
+        let inner = self.inner.read().unwrap();
+        inner.entries[inner.active].clone()
+    }
+
+    /// Finds a unit by its alias
+    pub fn find(&self, alias: &str) -> Option<UnitEntry> {
+        self.inner
+            .read()
+            .unwrap()
+            .entries
+            .iter()
+            .find(|e| e.alias.eq_ignore_ascii_case(alias))
+            .cloned()
+    }
+
+    /// Makes the unit with the alias the one commands use by default
+    pub fn set_active(&self, alias: &str) {
+        let mut inner = self.inner.write().unwrap();
+
+        if let Some(pos) = inner
+            .entries
+            .iter()
+            .position(|e| e.alias.eq_ignore_ascii_case(alias))
+        {
+            inner.active = pos;
+        }
+    }
+
+    /// Points the alias at another unit, monitors follow on their next poll
+    pub fn set_id(&self, alias: &str, id: &str) {
+        let mut inner = self.inner.write().unwrap();
+
+        if let Some(entry) = inner
+            .entries
+            .iter_mut()
+            .find(|e| e.alias.eq_ignore_ascii_case(alias))
+        {
+            entry.id = id.to_owned();
+        }
+    }
+
+    pub fn aliases(&self) -> Vec<String> {
+        self.all().into_iter().map(|e| e.alias).collect()
+    }
+
+    /// Prefix for chat messages so it's clear which unit they are about
+    pub fn tag(&self, entry: &UnitEntry) -> String {
+        if self.is_multi() {
+            format!("[{}] ", entry.alias)
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(alias: &str, id: &str) -> UnitEntry {
+        UnitEntry {
+            alias: alias.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn switches_the_active_unit() {
+        let units = Units::new(vec![entry("cam1", "boss1"), entry("cam2", "boss2")]);
+        assert_eq!(units.active().alias, "cam1");
+
+        units.set_active("CAM2");
+        assert_eq!(units.active().alias, "cam2");
+
+        units.set_active("cam3");
+        assert_eq!(units.active().alias, "cam2");
+    }
+
+    #[test]
+    fn points_an_alias_at_another_unit() {
+        let units = Units::new(vec![entry("cam1", "boss1"), entry("cam2", "boss2")]);
+
+        units.set_id("cam2", "boss3");
+
+        assert_eq!(units.find("cam2").map(|e| e.id).as_deref(), Some("boss3"));
+        assert_eq!(units.find("cam1").map(|e| e.id).as_deref(), Some("boss1"));
+        assert!(units.find("boss3").is_none());
+    }
+
+    #[test]
+    fn tags_only_with_multiple_units() {
+        let single = Units::new(vec![entry("liveu", "boss1")]);
+        let multi = Units::new(vec![entry("cam1", "boss1"), entry("cam2", "boss2")]);
+
+        assert!(!single.is_multi());
+        assert_eq!(single.tag(&single.active()), "");
+        assert!(multi.is_multi());
+        assert_eq!(multi.tag(&multi.active()), "[cam1] ");
+        assert_eq!(multi.aliases(), ["cam1", "cam2"]);
+    }
+}